@@ -0,0 +1,31 @@
+@group(0) @binding(0)
+var left_eye: texture_2d<f32>;
+@group(0) @binding(1)
+var right_eye: texture_2d<f32>;
+@group(0) @binding(2)
+var eye_sampler: sampler;
+
+struct VsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VsOut {
+    // Single fullscreen triangle.
+    let x = f32((index << 1u) & 2u);
+    let y = f32(index & 2u);
+    var out: VsOut;
+    out.position = vec4<f32>(x * 2.0 - 1.0, 1.0 - y * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(x, y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let left = textureSample(left_eye, eye_sampler, in.uv);
+    let right = textureSample(right_eye, eye_sampler, in.uv);
+    // Red channel from the left eye, cyan (green + blue) from the right.
+    // Alpha stays 1 over the scene and 0 over a transparent background.
+    return vec4<f32>(left.r, right.g, right.b, max(left.a, right.a));
+}
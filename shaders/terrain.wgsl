@@ -1,25 +1,77 @@
 struct Globals {
     view_proj: mat4x4<f32>,
     morph: vec4<f32>,
+    // xyz: offset, w: uniform scale placing this terrain in the scene
+    transform: vec4<f32>,
+    sun_dir: vec4<f32>,
+    camera_pos: vec4<f32>,
+    // x: 0 Lambert, 1 half-Lambert, 2 toon; y: toon band count
+    lighting: vec4<f32>,
+    // rgb: hemisphere ambient for up- and down-facing surfaces, intensity applied
+    sky_ambient: vec4<f32>,
+    ground_ambient: vec4<f32>,
+    // x: logarithmic depth enabled, y: 1 / log2(far + 1)
+    depth: vec4<f32>,
+    // x: beach max height, y: desert moisture max, z: semi-arid moisture max, w: temperature bias
+    biome: vec4<f32>,
+    // x: snow max, y: tundra max, z: taiga max, w: tropical min (temperatures)
+    climate: vec4<f32>,
+    // x: height amplitude, y: water level, z: biome blend width
+    shape: vec4<f32>,
+    // x: detail normal strength, y: detail repeats per world unit,
+    // z: detail mip bias, w: distance fade falloff (0 disables)
+    detail: vec4<f32>,
+    // xyz: base land color, used where no biome override applies
+    base: vec4<f32>,
+    // xyz: seabed color, w: transition depth below the water level
+    seabed: vec4<f32>,
+    // Specular strength of x: snow, y: rock, z: sand, w: vegetation
+    specular: vec4<f32>,
+    // x: object id written by the id pass
+    object: vec4<u32>,
 };
 
 const INV_TAU: f32 = 0.15915494;
+const BIOME_SHININESS: f32 = 24.0;
+// Biome blend half-width added at the full distance fade.
+const FAR_BLEND: f32 = 0.15;
 
 @group(0) @binding(0)
 var<uniform> globals: Globals;
+@group(0) @binding(1)
+var detail_map: texture_2d<f32>;
+@group(0) @binding(2)
+var detail_sampler: sampler;
 
 struct VsOut {
     @builtin(position) position: vec4<f32>,
     @location(0) normal: vec3<f32>,
-    @location(1) color: vec3<f32>,
+    @location(1) temperature: f32,
+    @location(2) height: f32,
+    @location(3) moisture: f32,
+    @location(4) paint: vec4<f32>,
+    @location(5) world_pos: vec3<f32>,
+    // Local up: away from the globe's center, or +Y on the flat map.
+    @location(6) up: vec3<f32>,
 };
 
+fn apply_depth_mode(clip: vec4<f32>) -> vec4<f32> {
+    if globals.depth.x < 0.5 {
+        return clip;
+    }
+    // Logarithmic depth: z/w = log2(1 + w) / log2(1 + far).
+    let z = log2(max(1e-6, 1.0 + clip.w)) * globals.depth.y * clip.w;
+    return vec4<f32>(clip.xy, z, clip.w);
+}
+
 @vertex
 fn vs_main(
     @location(0) pos: vec3<f32>,
     @location(1) normal: vec3<f32>,
-    @location(2) color: vec3<f32>,
-    @location(3) flat_pos: vec3<f32>,
+    @location(2) flat_pos: vec3<f32>,
+    @location(3) moisture: f32,
+    @location(4) paint: vec4<f32>,
+    @location(5) temperature: f32,
 ) -> VsOut {
     var out: VsOut;
     let t = clamp(globals.morph.x, 0.0, 1.0);
@@ -37,21 +89,178 @@ fn vs_main(
     let flat_x = (u - 0.5) * map_width;
     let flat_z = (0.5 - v) * map_height;
     let flat_world = vec3<f32>(flat_x, height, flat_z);
-    let world_pos = globe_pos * (1.0 - t) + flat_world * t;
+    let local_pos = globe_pos * (1.0 - t) + flat_world * t;
+    let world_pos = local_pos * globals.transform.w + globals.transform.xyz;
     let world_normal = normalize(globe_normal * (1.0 - t) + vec3<f32>(0.0, 1.0, 0.0) * t);
-    out.position = globals.view_proj * vec4<f32>(world_pos, 1.0);
+    out.position = apply_depth_mode(globals.view_proj * vec4<f32>(world_pos, 1.0));
     out.normal = world_normal;
-    out.color = color;
+    out.temperature = temperature;
+    out.height = height;
+    out.moisture = moisture;
+    out.paint = paint;
+    out.world_pos = world_pos;
+    out.up = normalize(normalize(globe_pos) * (1.0 - t) + vec3<f32>(0.0, 1.0, 0.0) * t);
     return out;
 }
 
+// Coverage of `x < edge`: a hard step, or a smoothstep across `width`
+// either side of the threshold.
+fn below(x: f32, edge: f32, width: f32) -> f32 {
+    if width <= 0.0 {
+        return select(0.0, 1.0, x < edge);
+    }
+    return 1.0 - smoothstep(edge - width, edge + width, x);
+}
+
+// Coverage of `x > edge`, the mirror of `below`.
+fn above(x: f32, edge: f32, width: f32) -> f32 {
+    if width <= 0.0 {
+        return select(0.0, 1.0, x > edge);
+    }
+    return smoothstep(edge - width, edge + width, x);
+}
+
+// rgb: albedo, a: specular strength (`globals.specular` by biome group).
+// Biome overrides in priority order, the first that applies winning; land
+// none of them claims keeps the base color. Mirrors `Biome::classify`.
+// Each override is laid over the ones after it by its soft coverage, so with
+// a zero blend width this is exactly that first-match choice.
+fn biome_material(height: f32, temperature: f32, moisture: f32, blend: f32) -> vec4<f32> {
+    let h = height / globals.shape.x;
+    let temp = clamp(temperature + globals.biome.w, 0.0, 1.0);
+    let tropical = above(temp, globals.climate.w, blend);
+    let semi_arid = below(moisture, globals.biome.z, blend);
+    var overrides = array<vec4<f32>, 12>(
+        // Deep ocean
+        vec4<f32>(0.04, 0.12, 0.24, 0.0),
+        // Ocean
+        vec4<f32>(0.03, 0.1, 0.22, 0.0),
+        // Shallows
+        vec4<f32>(0.06, 0.22, 0.35, 0.0),
+        // Beach
+        vec4<f32>(0.78, 0.72, 0.54, globals.specular.z),
+        // Snow
+        vec4<f32>(0.9, 0.92, 0.96, globals.specular.x),
+        // Rock
+        vec4<f32>(0.48, 0.46, 0.44, globals.specular.y),
+        // Tundra
+        vec4<f32>(0.62, 0.66, 0.6, globals.specular.w),
+        // Desert
+        vec4<f32>(0.8, 0.72, 0.45, globals.specular.z),
+        // Taiga
+        vec4<f32>(0.14, 0.33, 0.26, globals.specular.w),
+        // Savanna
+        vec4<f32>(0.6, 0.58, 0.3, globals.specular.w),
+        // Grassland
+        vec4<f32>(0.22, 0.56, 0.28, globals.specular.w),
+        // Rainforest
+        vec4<f32>(0.04, 0.36, 0.12, globals.specular.w),
+    );
+    var coverage = array<f32, 12>(
+        below(h, -0.45, blend),
+        below(h, -0.2, blend),
+        below(h, 0.0, blend),
+        below(h, globals.biome.x, blend),
+        max(above(h, 0.85, blend), below(temp, globals.climate.x, blend)),
+        above(h, 0.65, blend),
+        below(temp, globals.climate.y, blend),
+        below(moisture, globals.biome.y, blend),
+        below(temp, globals.climate.z, blend),
+        semi_arid * tropical,
+        semi_arid,
+        tropical,
+    );
+    var material = vec4<f32>(globals.base.rgb, globals.specular.w);
+    for (var i = 11; i >= 0; i--) {
+        material = mix(material, overrides[i], coverage[i]);
+    }
+    return material;
+}
+
+fn detail_sample(uv: vec2<f32>, strength: f32) -> vec3<f32> {
+    let t = textureSampleBias(detail_map, detail_sampler, uv, globals.detail.z).xyz * 2.0 - 1.0;
+    return vec3<f32>(t.xy * strength, t.z);
+}
+
+// Triplanar detail normal using a whiteout blend: each projection's tangent
+// normal is swizzled onto its axis and added to the geometric normal, so a
+// strength of 0 returns `n` unchanged.
+fn detail_normal(p: vec3<f32>, n: vec3<f32>, strength: f32) -> vec3<f32> {
+    var w = pow(abs(n), vec3<f32>(4.0));
+    w = w / (w.x + w.y + w.z);
+    let scale = globals.detail.y;
+    let tx = detail_sample(p.zy * scale, strength);
+    let ty = detail_sample(p.xz * scale, strength);
+    let tz = detail_sample(p.xy * scale, strength);
+    let nx = vec3<f32>(tx.xy + n.zy, abs(tx.z) * n.x).zyx;
+    let ny = vec3<f32>(ty.xy + n.xz, abs(ty.z) * n.y).xzy;
+    let nz = vec3<f32>(tz.xy + n.xy, abs(tz.z) * n.z);
+    return normalize(nx * w.x + ny * w.y + nz * w.z);
+}
+
+// 0 at the camera rising to 1 at the falloff distance `globals.detail.w`.
+// Quadratic, so the foreground keeps its crisp biome edges and detail.
+fn distance_fade(distance: f32) -> f32 {
+    let falloff = globals.detail.w;
+    if falloff <= 0.0 {
+        return 0.0;
+    }
+    let t = clamp(distance / falloff, 0.0, 1.0);
+    return t * t;
+}
+
+// Diffuse sun term in [0, 1] for the selected lighting model.
+fn sun_light(n: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+    let ndl = dot(n, light_dir);
+    let model = globals.lighting.x;
+    if model < 0.5 {
+        return clamp(ndl, 0.0, 1.0);
+    }
+    if model < 1.5 {
+        let wrapped = ndl * 0.5 + 0.5;
+        return wrapped * wrapped;
+    }
+    // Toon: `bands` flat levels from unlit to fully lit.
+    let bands = max(globals.lighting.y, 2.0);
+    let level = min(floor(clamp(ndl, 0.0, 1.0) * bands), bands - 1.0);
+    return level / (bands - 1.0);
+}
+
+// Hemisphere ambient: sky color on faces turned up, ground color on faces
+// turned down, blended across the horizon.
+fn hemisphere_ambient(n: vec3<f32>, up: vec3<f32>) -> vec3<f32> {
+    let sky = dot(n, up) * 0.5 + 0.5;
+    return mix(globals.ground_ambient.rgb, globals.sky_ambient.rgb, sky);
+}
+
 @fragment
 fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
-    let light_dir = normalize(vec3<f32>(0.4, 0.9, 0.2));
-    let n = normalize(in.normal);
-    let ndl = clamp(dot(n, light_dir), 0.0, 1.0);
-    let ambient = 0.45;
-    let diffuse = ndl * 0.55;
+    let light_dir = normalize(globals.sun_dir.xyz);
+    // Far fragments cover many biome edges and detail texels; blending wider
+    // and flattening the detail there keeps the horizon from shimmering.
+    let fade = distance_fade(distance(globals.camera_pos.xyz, in.world_pos));
+    let n = detail_normal(in.world_pos, normalize(in.normal), globals.detail.x * (1.0 - fade));
+    let ambient = hemisphere_ambient(n, normalize(in.up));
+    let diffuse = sun_light(n, light_dir) * 0.55;
     let shading = ambient + diffuse;
-    return vec4<f32>(in.color * shading, 1.0);
+    let blend = globals.shape.z + fade * FAR_BLEND;
+    let material = biome_material(in.height, in.temperature, in.moisture, blend);
+    let surface = mix(material.rgb, in.paint.rgb, in.paint.a);
+    // Submerged land fades toward the seabed tone with depth so hills under
+    // the water don't show through as grass.
+    let depth = globals.shape.y - in.height;
+    let submerged = smoothstep(0.0, max(globals.seabed.w, 1e-4), depth);
+    let color = mix(surface, globals.seabed.rgb, submerged);
+    // Blinn-Phong glint; paint and the seabed are matte.
+    let view_dir = normalize(globals.camera_pos.xyz - in.world_pos);
+    let half_dir = normalize(light_dir + view_dir);
+    let lit = select(0.0, 1.0, dot(n, light_dir) > 0.0);
+    let strength = material.a * (1.0 - in.paint.a) * (1.0 - submerged) * lit;
+    let specular = pow(max(dot(n, half_dir), 0.0), BIOME_SHININESS) * strength;
+    return vec4<f32>(color * shading + vec3<f32>(specular), 1.0);
+}
+
+@fragment
+fn fs_id(in: VsOut) -> @location(0) u32 {
+    return globals.object.x;
 }
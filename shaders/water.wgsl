@@ -1,17 +1,115 @@
 struct Globals {
     view_proj: mat4x4<f32>,
     morph: vec4<f32>,
+    camera_pos: vec4<f32>,
+    sun_dir: vec4<f32>,
+    // x: logarithmic depth enabled, y: 1 / log2(far + 1)
+    depth: vec4<f32>,
+    // rgb: deep-water color, a: opacity
+    color: vec4<f32>,
+    specular: vec4<f32>,
+    // x: globe radius, y: shoreline edge softness,
+    // z: water mask class to keep (0 keeps all water without reading the mask)
+    shore: vec4<f32>,
+    // x: waves enabled, y: time in seconds, z: amplitude, w: longest wavelength
+    waves: vec4<f32>,
+    // xy: travel direction (x east, y north), z: choppiness,
+    // w: mesh vertex spacing
+    wave_shape: vec4<f32>,
 };
 
 const INV_TAU: f32 = 0.15915494;
+const TAU: f32 = 6.2831853;
+const GRAVITY: f32 = 9.8;
+const WAVE_COUNT: i32 = 3;
+// Per-wave heading offset (radians), wavelength and amplitude relative to the
+// primary wave; slightly spread so the sum doesn't look like a single sine.
+const WAVE_ANGLES = array<f32, 3>(0.0, 0.6, -0.45);
+const WAVE_LENGTHS = array<f32, 3>(1.0, 0.62, 0.41);
+const WAVE_AMPLITUDES = array<f32, 3>(1.0, 0.5, 0.3);
 
 @group(0) @binding(0)
 var<uniform> globals: Globals;
+@group(0) @binding(1)
+var terrain_heights: texture_2d<f32>;
+// Per terrain vertex: 0 dry, 1 ocean, 2 lake.
+@group(0) @binding(2)
+var water_mask: texture_2d<u32>;
 
 struct VsOut {
     @builtin(position) position: vec4<f32>,
+    @location(0) world_pos: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) surface: f32,
+    // Undisplaced globe and flat-map positions the waves are evaluated at.
+    @location(4) wave_globe: vec3<f32>,
+    @location(5) wave_flat: vec3<f32>,
 };
 
+struct Gerstner {
+    offset: vec3<f32>,
+    normal: vec3<f32>,
+};
+
+// Sum of Gerstner waves at rest position `p` on a surface with unit normal
+// `n`. Each wave's fronts are planes perpendicular to a fixed direction; it
+// travels along that direction projected into the tangent plane, so on the
+// globe the horizontal displacement follows the surface instead of pointing
+// off it. With `geometry` set, waves too short for the mesh to resolve fade out
+// of the offset instead of aliasing; the normal always includes every wave.
+fn gerstner(p: vec3<f32>, n: vec3<f32>, geometry: bool) -> Gerstner {
+    var out: Gerstner;
+    out.offset = vec3<f32>(0.0);
+    var tilt = vec3<f32>(0.0);
+    var squash = 0.0;
+    let flat_dir = globals.wave_shape.xy + vec2<f32>(1e-6, 0.0);
+    let heading = normalize(vec3<f32>(flat_dir.x, 0.0, flat_dir.y));
+    let time = globals.waves.y;
+    // Local copies: constant arrays can't be indexed dynamically.
+    var angles = WAVE_ANGLES;
+    var lengths = WAVE_LENGTHS;
+    var amplitudes = WAVE_AMPLITUDES;
+    for (var i = 0; i < WAVE_COUNT; i++) {
+        let c = cos(angles[i]);
+        let s = sin(angles[i]);
+        let dir = vec3<f32>(heading.x * c - heading.z * s, 0.0, heading.x * s + heading.z * c);
+        let wavelength = globals.waves.w * lengths[i];
+        var amplitude = globals.waves.z * amplitudes[i];
+        if geometry {
+            amplitude *= smoothstep(2.0, 4.0, wavelength / globals.wave_shape.w);
+        }
+        let k = TAU / wavelength;
+        let phase = k * dot(dir, p) - sqrt(GRAVITY * k) * time;
+        let along = dir - n * dot(dir, n);
+        let along_len = length(along);
+        let t_hat = along / max(along_len, 1e-4);
+        // Phase gradient along the surface; shrinks where the fronts run
+        // nearly parallel to it.
+        let k_surface = k * along_len;
+        let ka = k_surface * amplitude;
+        let q = min(globals.wave_shape.z, 1.0 / max(ka * f32(WAVE_COUNT), 1e-4));
+        out.offset += t_hat * (q * amplitude * cos(phase)) + n * (amplitude * sin(phase));
+        tilt += t_hat * (ka * cos(phase));
+        squash += q * ka * sin(phase);
+    }
+    out.normal = normalize(n * (1.0 - squash) - tilt);
+    return out;
+}
+
+fn rotate_y(v: vec3<f32>, c: f32, s: f32) -> vec3<f32> {
+    return vec3<f32>(v.x * c + v.z * s, v.y, -v.x * s + v.z * c);
+}
+
+fn apply_depth_mode(clip: vec4<f32>) -> vec4<f32> {
+    if globals.depth.x < 0.5 {
+        return clip;
+    }
+    // Logarithmic depth: z/w = log2(1 + w) / log2(1 + far).
+    let z = log2(max(1e-6, 1.0 + clip.w)) * globals.depth.y * clip.w;
+    return vec4<f32>(clip.xy, z, clip.w);
+}
+
 @vertex
 fn vs_main(
     @location(0) pos: vec3<f32>,
@@ -23,7 +121,6 @@ fn vs_main(
     let rot_blend = rot * t;
     let c = cos(rot_blend);
     let s = sin(rot_blend);
-    let globe_pos = vec3<f32>(pos.x * c + pos.z * s, pos.y, -pos.x * s + pos.z * c);
     let map_width = globals.morph.z;
     let map_height = globals.morph.w;
     let u = fract(flat_pos.x + rot * INV_TAU);
@@ -31,14 +128,95 @@ fn vs_main(
     let height = flat_pos.z;
     let flat_x = (u - 0.5) * map_width;
     let flat_z = (0.5 - v) * map_height;
-    let flat_world = vec3<f32>(flat_x, height, flat_z);
+    // Flat-map waves use displayed coordinates so the phase only breaks at
+    // the map's edge, never across the middle.
+    let wave_flat = vec3<f32>(flat_x, 0.0, flat_z);
+    var globe_rest = pos;
+    var flat_world = vec3<f32>(flat_x, height, flat_z);
+    if globals.waves.x > 0.5 {
+        globe_rest += gerstner(pos, normalize(pos), true).offset;
+        flat_world += gerstner(wave_flat, vec3<f32>(0.0, 1.0, 0.0), true).offset;
+    }
+    let globe_pos = rotate_y(globe_rest, c, s);
     let world_pos = globe_pos * (1.0 - t) + flat_world * t;
-    out.position = globals.view_proj * vec4<f32>(world_pos, 1.0);
+    out.position = apply_depth_mode(globals.view_proj * vec4<f32>(world_pos, 1.0));
+    out.world_pos = world_pos;
+    out.normal = normalize(normalize(globe_pos) * (1.0 - t) + vec3<f32>(0.0, 1.0, 0.0) * t);
+    out.uv = flat_pos.xy;
+    out.surface = (length(globe_rest) - globals.shore.x) * (1.0 - t) + flat_world.y * t;
+    out.wave_globe = pos;
+    out.wave_flat = wave_flat;
     return out;
 }
 
+fn terrain_height(uv: vec2<f32>) -> f32 {
+    let dims = vec2<i32>(textureDimensions(terrain_heights));
+    let p = clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * vec2<f32>(dims - 1);
+    let i = min(vec2<i32>(floor(p)), dims - 2);
+    let f = p - vec2<f32>(i);
+    let h00 = textureLoad(terrain_heights, i, 0).r;
+    let h10 = textureLoad(terrain_heights, i + vec2<i32>(1, 0), 0).r;
+    let h01 = textureLoad(terrain_heights, i + vec2<i32>(0, 1), 0).r;
+    let h11 = textureLoad(terrain_heights, i + vec2<i32>(1, 1), 0).r;
+    return mix(mix(h00, h10, f.x), mix(h01, h11, f.x), f.y);
+}
+
+fn kept(texel: vec2<i32>, body: u32) -> f32 {
+    return select(0.0, 1.0, textureLoad(water_mask, texel, 0).r == body);
+}
+
+// Share of the water of class `shore.z` around `uv`, bilinear between the
+// four nearest mask texels so basin edges don't show the grid.
+fn mask_coverage(uv: vec2<f32>) -> f32 {
+    let body = u32(globals.shore.z);
+    if body == 0u {
+        return 1.0;
+    }
+    let dims = vec2<i32>(textureDimensions(water_mask));
+    let p = clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * vec2<f32>(dims - 1);
+    let i = min(vec2<i32>(floor(p)), dims - 2);
+    let f = p - vec2<f32>(i);
+    let m00 = kept(i, body);
+    let m10 = kept(i + vec2<i32>(1, 0), body);
+    let m01 = kept(i + vec2<i32>(0, 1), body);
+    let m11 = kept(i + vec2<i32>(1, 1), body);
+    return mix(mix(m00, m10, f.x), mix(m01, m11, f.x), f.y);
+}
+
 @fragment
-fn fs_main() -> @location(0) vec4<f32> {
-    // Soft blue-green with stronger opacity for oceans.
-    return vec4<f32>(0.08, 0.32, 0.5, 0.55);
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    var base = globals.color;
+
+    // Fade out where the water is barely above the terrain, over a width
+    // measured in screen-space derivatives so grazing angles stay stable.
+    let depth = in.surface - terrain_height(in.uv);
+    let edge = max(fwidth(depth) * globals.shore.y, 1e-4);
+    base.a *= smoothstep(0.0, edge, depth);
+
+    // Drop water outside the bodies the mask keeps, after the derivatives
+    // above so discarding can't disturb them.
+    let coverage = mask_coverage(in.uv);
+    if coverage <= 0.0 {
+        discard;
+    }
+    base.a *= coverage;
+
+    // Blinn-Phong glint from the sun, added on top of the base color. Wave
+    // normals are evaluated per fragment, so they stay crisp even where the
+    // mesh is too coarse to displace them.
+    var n = normalize(in.normal);
+    if globals.waves.x > 0.5 {
+        let t = clamp(globals.morph.x, 0.0, 1.0);
+        let rot_blend = globals.morph.y * t;
+        let globe = gerstner(in.wave_globe, normalize(in.wave_globe), false).normal;
+        let globe_n = rotate_y(globe, cos(rot_blend), sin(rot_blend));
+        let flat_n = gerstner(in.wave_flat, vec3<f32>(0.0, 1.0, 0.0), false).normal;
+        n = normalize(globe_n * (1.0 - t) + flat_n * t);
+    }
+    let light_dir = normalize(globals.sun_dir.xyz);
+    let view_dir = normalize(globals.camera_pos.xyz - in.world_pos);
+    let half_dir = normalize(light_dir + view_dir);
+    let facing = step(0.0, dot(n, light_dir));
+    let spec = pow(max(dot(n, half_dir), 0.0), globals.specular.y) * globals.specular.x * facing * coverage;
+    return vec4<f32>(base.rgb + vec3<f32>(spec), clamp(base.a + spec, 0.0, 1.0));
 }
@@ -0,0 +1,154 @@
+struct Globals {
+    view_proj: mat4x4<f32>,
+    camera_pos: vec4<f32>,
+    // xyz = region center, w = half extent of the region box.
+    region: vec4<f32>,
+    // xyz = fall direction, w = 1 for snow, 0 for rain.
+    fall: vec4<f32>,
+    // x: logarithmic depth enabled, y: 1 / log2(far + 1)
+    depth: vec4<f32>,
+    // rgb = particle color, a = opacity.
+    color: vec4<f32>,
+};
+
+struct Step {
+    region: vec4<f32>,
+    fall: vec4<f32>,
+    // x = seconds since the last step, y = animation time, z = particle count.
+    motion: vec4<f32>,
+};
+
+// xyz = world position, w = per-particle seed in (0, 1]; 0 until spawned.
+struct Particle {
+    pos: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+@group(0) @binding(1)
+var<storage, read> particles: array<Particle>;
+@group(0) @binding(2)
+var<uniform> step: Step;
+@group(0) @binding(3)
+var<storage, read_write> sim_particles: array<Particle>;
+
+const RAIN_SPEED: f32 = 28.0;
+const SNOW_SPEED: f32 = 2.5;
+const SNOW_SWAY: f32 = 0.8;
+const RAIN_SIZE: vec2<f32> = vec2<f32>(0.04, 0.9);
+const SNOW_SIZE: vec2<f32> = vec2<f32>(0.22, 0.22);
+
+fn hash(n: u32) -> f32 {
+    var x = n * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    x = (x >> 22u) ^ x;
+    return f32(x) / 4294967295.0;
+}
+
+// Wraps `offset` into the region box, one axis at a time, so particles that
+// fall out of the bottom or are left behind by a moving camera re-enter on
+// the far side.
+fn wrap(offset: vec3<f32>, half: f32) -> vec3<f32> {
+    let size = 2.0 * half;
+    return offset - size * floor((offset + vec3<f32>(half)) / size);
+}
+
+fn tangent(fall: vec3<f32>) -> vec3<f32> {
+    let axis = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(fall.x) > 0.9);
+    return normalize(cross(fall, axis));
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= u32(step.motion.z) {
+        return;
+    }
+    let center = step.region.xyz;
+    let half = step.region.w;
+    let fall = step.fall.xyz;
+    var particle = sim_particles[index];
+    if particle.pos.w <= 0.0 {
+        let spawn = vec3<f32>(hash(index * 4u), hash(index * 4u + 1u), hash(index * 4u + 2u));
+        particle.pos = vec4<f32>(center + (spawn * 2.0 - 1.0) * half, max(hash(index * 4u + 3u), 1e-3));
+    }
+    let seed = particle.pos.w;
+    let snow = step.fall.w;
+    // Vary speeds a little so the particles don't move as one sheet.
+    let speed = mix(RAIN_SPEED, SNOW_SPEED, snow) * (0.75 + 0.5 * seed);
+    var velocity = fall * speed;
+    let phase = step.motion.y * (1.0 + seed) + seed * 6.2831853;
+    let side = tangent(fall);
+    let ahead = cross(fall, side);
+    velocity += (side * sin(phase) + ahead * cos(phase * 0.7)) * SNOW_SWAY * snow;
+    let moved = particle.pos.xyz + velocity * step.motion.x;
+    particle.pos = vec4<f32>(center + wrap(moved - center, half), seed);
+    sim_particles[index] = particle;
+}
+
+fn apply_depth_mode(clip: vec4<f32>) -> vec4<f32> {
+    if globals.depth.x < 0.5 {
+        return clip;
+    }
+    // Logarithmic depth: z/w = log2(1 + w) / log2(1 + far).
+    let z = log2(max(1e-6, 1.0 + clip.w)) * globals.depth.y * clip.w;
+    return vec4<f32>(clip.xy, z, clip.w);
+}
+
+struct VsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) fade: f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, @builtin(instance_index) instance: u32) -> VsOut {
+    // Two triangles per particle, as corners of a unit quad.
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex];
+    let particle = particles[instance];
+    let center = particle.pos.xyz;
+    let fall = globals.fall.xyz;
+    let snow = globals.fall.w;
+
+    // Billboards turn about the fall direction to face the camera, so rain
+    // stays a streak along its motion.
+    let to_eye = globals.camera_pos.xyz - center;
+    var side = cross(fall, to_eye);
+    if dot(side, side) < 1e-8 {
+        side = tangent(fall);
+    }
+    side = normalize(side);
+    let size = mix(RAIN_SIZE, SNOW_SIZE, snow);
+    let world = center + side * corner.x * size.x - fall * corner.y * size.y;
+
+    var out: VsOut;
+    out.position = apply_depth_mode(globals.view_proj * vec4<f32>(world, 1.0));
+    // Thin out towards the region's edge so the box itself never shows.
+    let half = globals.region.w;
+    let reach = length(center - globals.region.xyz) / half;
+    out.fade = 1.0 - smoothstep(0.6, 1.0, reach);
+    out.corner = corner;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let snow = globals.fall.w;
+    // Round flakes, and streaks that taper towards both ends.
+    let flake = 1.0 - smoothstep(0.6, 1.0, length(in.corner));
+    let streak = (1.0 - abs(in.corner.y)) * (1.0 - abs(in.corner.x));
+    let shape = mix(streak, flake, snow);
+    let alpha = globals.color.a * shape * in.fade;
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(globals.color.rgb, alpha);
+}
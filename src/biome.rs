@@ -0,0 +1,148 @@
+use crate::terrain::{TerrainSettings, MAX_BIOME_BLEND};
+
+/// Blend half-width handed to the shader for `TerrainSettings::biome_blend`.
+/// A NaN or infinite width would turn every smoothstep into NaN, so wider
+/// values are clamped to `MAX_BIOME_BLEND`, and NaN or non-positive ones give
+/// the hard edges of `Biome::classify`.
+pub fn blend_width(biome_blend: f32) -> f32 {
+    if biome_blend > 0.0 {
+        biome_blend.min(MAX_BIOME_BLEND)
+    } else {
+        0.0
+    }
+}
+
+/// Biome classes produced by `biome_material` in `shaders/terrain.wgsl`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Biome {
+    DeepOcean,
+    Ocean,
+    Shallows,
+    Beach,
+    Snow,
+    Rock,
+    Tundra,
+    Desert,
+    Taiga,
+    Savanna,
+    Grassland,
+    Rainforest,
+    /// No override applies; drawn in `TerrainSettings::base_color`.
+    Forest,
+}
+
+impl Biome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Biome::DeepOcean => "deep_ocean",
+            Biome::Ocean => "ocean",
+            Biome::Shallows => "shallows",
+            Biome::Beach => "beach",
+            Biome::Snow => "snow",
+            Biome::Rock => "rock",
+            Biome::Tundra => "tundra",
+            Biome::Desert => "desert",
+            Biome::Taiga => "taiga",
+            Biome::Savanna => "savanna",
+            Biome::Grassland => "grassland",
+            Biome::Rainforest => "rainforest",
+            Biome::Forest => "forest",
+        }
+    }
+
+    /// CPU copy of the shader's classifier; the two must change together.
+    /// `temperature` is the unbiased per-vertex value, and `height_amplitude`
+    /// the world's, which the height bands are fractions of. Every vertex gets a
+    /// class: the first override that applies, else the base class. The
+    /// shader's `biome_blend` only softens colors between classes, so this
+    /// stays the hard choice.
+    pub fn classify(
+        height: f32,
+        temperature: f32,
+        moisture: f32,
+        height_amplitude: f32,
+        settings: &TerrainSettings,
+    ) -> Self {
+        let h = height / height_amplitude;
+        let temp = (temperature + settings.temperature_bias).clamp(0.0, 1.0);
+        let tropical = temp > settings.tropical_temperature_min;
+        let semi_arid = moisture < settings.semi_arid_moisture_max;
+        let snowy = h > 0.85 || temp < settings.snow_temperature_max;
+        // In priority order; the first that applies wins.
+        let overrides = [
+            (h < -0.45, Biome::DeepOcean),
+            (h < -0.2, Biome::Ocean),
+            (h < 0.0, Biome::Shallows),
+            (h < settings.beach_max_height, Biome::Beach),
+            (snowy, Biome::Snow),
+            (h > 0.65, Biome::Rock),
+            (temp < settings.tundra_temperature_max, Biome::Tundra),
+            (moisture < settings.desert_moisture_max, Biome::Desert),
+            (temp < settings.taiga_temperature_max, Biome::Taiga),
+            (semi_arid && tropical, Biome::Savanna),
+            (semi_arid, Biome::Grassland),
+            (tropical, Biome::Rainforest),
+        ];
+        overrides
+            .into_iter()
+            .find_map(|(applies, biome)| applies.then_some(biome))
+            .unwrap_or(Biome::Forest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds_fall_on_the_lower_priority_side() {
+        // A unit amplitude and no bias keep the inputs exactly on the
+        // thresholds, which are strict as in the shader's hard step.
+        let settings = TerrainSettings {
+            temperature_bias: 0.0,
+            ..TerrainSettings::default()
+        };
+        let classify = |h, temp, moisture| Biome::classify(h, temp, moisture, 1.0, &settings);
+        let (temp, moisture) = (0.5, 0.5);
+        let heights = [
+            (-0.46, Biome::DeepOcean),
+            (-0.45, Biome::Ocean),
+            (-0.2, Biome::Shallows),
+            (0.0, Biome::Beach),
+            (settings.beach_max_height, Biome::Forest),
+            (0.65, Biome::Forest),
+            (0.66, Biome::Rock),
+            (0.85, Biome::Rock),
+            (0.86, Biome::Snow),
+        ];
+        for (h, biome) in heights {
+            assert_eq!(classify(h, temp, moisture), biome, "height {h}");
+        }
+        let temperatures = [
+            (settings.snow_temperature_max, Biome::Tundra),
+            (settings.tundra_temperature_max, Biome::Taiga),
+            (settings.taiga_temperature_max, Biome::Forest),
+            (settings.tropical_temperature_min, Biome::Forest),
+            (settings.tropical_temperature_min + 0.01, Biome::Rainforest),
+        ];
+        for (temp, biome) in temperatures {
+            assert_eq!(classify(0.3, temp, moisture), biome, "temperature {temp}");
+        }
+        let moistures = [
+            (settings.desert_moisture_max, Biome::Grassland),
+            (settings.semi_arid_moisture_max, Biome::Forest),
+        ];
+        for (moisture, biome) in moistures {
+            assert_eq!(classify(0.3, temp, moisture), biome, "moisture {moisture}");
+        }
+    }
+
+    #[test]
+    fn unusable_blend_widths_fall_back_to_hard_edges() {
+        assert_eq!(blend_width(0.05), 0.05);
+        assert_eq!(blend_width(0.0), 0.0);
+        assert_eq!(blend_width(-0.1), 0.0);
+        assert_eq!(blend_width(f32::NAN), 0.0);
+        assert_eq!(blend_width(f32::INFINITY), MAX_BIOME_BLEND);
+    }
+}
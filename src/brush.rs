@@ -0,0 +1,90 @@
+pub const DEFAULT_RADIUS: f32 = 8.0;
+pub const DEFAULT_OPACITY: f32 = 0.8;
+/// About three grid cells on the default globe.
+pub const DEFAULT_FEATHER: f32 = 4.0;
+/// Fraction of the way each dab moves a vertex towards the brush target.
+pub const FLOW: f32 = 0.35;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BrushMode {
+    Off,
+    Paint,
+    Erase,
+    /// Rerolls the terrain under the brush from fresh seeds, once per click.
+    Regenerate,
+}
+
+impl BrushMode {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub const ALL: [BrushMode; 4] = [
+        BrushMode::Off,
+        BrushMode::Paint,
+        BrushMode::Erase,
+        BrushMode::Regenerate,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            BrushMode::Off => BrushMode::Paint,
+            BrushMode::Paint => BrushMode::Erase,
+            BrushMode::Erase => BrushMode::Regenerate,
+            BrushMode::Regenerate => BrushMode::Off,
+        }
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn label(self) -> &'static str {
+        match self {
+            BrushMode::Off => "Off",
+            BrushMode::Paint => "Paint",
+            BrushMode::Erase => "Erase",
+            BrushMode::Regenerate => "Regenerate",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BrushSettings {
+    pub mode: BrushMode,
+    /// World-space radius measured along the globe surface.
+    pub radius: f32,
+    pub color: [f32; 3],
+    pub opacity: f32,
+    /// Width of the rim over which a regenerated area blends into the
+    /// terrain around it, in the same units as `radius`.
+    pub feather: f32,
+}
+
+impl Default for BrushSettings {
+    fn default() -> Self {
+        Self {
+            mode: BrushMode::Off,
+            radius: DEFAULT_RADIUS,
+            color: [0.75, 0.25, 0.2],
+            opacity: DEFAULT_OPACITY,
+            feather: DEFAULT_FEATHER,
+        }
+    }
+}
+
+/// Smooth falloff from 1 at the brush centre to 0 at its rim.
+pub fn falloff(distance: f32, radius: f32) -> f32 {
+    if radius <= 0.0 || distance >= radius {
+        return 0.0;
+    }
+    let t = 1.0 - distance / radius;
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Full weight out to `radius - feather`, easing to 0 at `radius`.
+pub fn feathered(distance: f32, radius: f32, feather: f32) -> f32 {
+    let feather = feather.clamp(0.0, radius);
+    if distance >= radius {
+        return 0.0;
+    }
+    if feather <= 0.0 || distance <= radius - feather {
+        return 1.0;
+    }
+    let t = (radius - distance) / feather;
+    t * t * (3.0 - 2.0 * t)
+}
@@ -1,3 +1,94 @@
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// How scene depth is distributed over the depth buffer.
+///
+/// `Standard` packs most precision right in front of the near plane, which is
+/// what makes terrain and water z-fight on a distant globe. `Logarithmic`
+/// rewrites clip-space z in the vertex shaders so precision is spread evenly
+/// in log space; it needs no pipeline changes but interpolates depth per
+/// vertex, so very large triangles crossing the near plane can clip early.
+/// `ReversedZ` swaps near and far in the projection, clears to 0 and tests
+/// with `Greater`; with a float depth buffer it gives near-uniform precision
+/// without touching the shaders.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DepthMode {
+    Standard,
+    Logarithmic,
+    ReversedZ,
+}
+
+impl DepthMode {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub const ALL: [DepthMode; 3] = [
+        DepthMode::Standard,
+        DepthMode::Logarithmic,
+        DepthMode::ReversedZ,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            DepthMode::Standard => DepthMode::Logarithmic,
+            DepthMode::Logarithmic => DepthMode::ReversedZ,
+            DepthMode::ReversedZ => DepthMode::Standard,
+        }
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn label(self) -> &'static str {
+        match self {
+            DepthMode::Standard => "Standard",
+            DepthMode::Logarithmic => "Logarithmic",
+            DepthMode::ReversedZ => "Reversed-Z",
+        }
+    }
+
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthMode::ReversedZ => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    pub fn is_reversed(self) -> bool {
+        self == DepthMode::ReversedZ
+    }
+
+    /// Shader parameters: x enables log depth, y is `1 / log2(far + 1)`.
+    pub fn uniform(self, far: f32) -> [f32; 4] {
+        let log = if self == DepthMode::Logarithmic {
+            1.0
+        } else {
+            0.0
+        };
+        [log, 1.0 / (far + 1.0).log2(), 0.0, 0.0]
+    }
+}
+
+/// Depth compare functions for the standard and reversed pipelines.
+pub const COMPARES: [wgpu::CompareFunction; 2] =
+    [wgpu::CompareFunction::Less, wgpu::CompareFunction::Greater];
+
+/// Polygon offset that pushes a surface away from the camera under
+/// `compare`, so it loses depth ties with coincident geometry. `constant` is
+/// in units of the depth format's precision at the triangle's depth and
+/// `slope_scale` multiplies the triangle's steepest depth slope.
+pub fn bias_away(
+    compare: wgpu::CompareFunction,
+    constant: i32,
+    slope_scale: f32,
+) -> wgpu::DepthBiasState {
+    let sign = if compare == wgpu::CompareFunction::Greater {
+        -1
+    } else {
+        1
+    };
+    wgpu::DepthBiasState {
+        constant: constant * sign,
+        slope_scale: slope_scale * sign as f32,
+        clamp: 0.0,
+    }
+}
+
 pub struct DepthTexture {
     pub view: wgpu::TextureView,
 }
@@ -14,7 +105,7 @@ impl DepthTexture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
+            format: FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         };
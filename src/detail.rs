@@ -0,0 +1,184 @@
+use glam::Vec3;
+use rand::{Rng, SeedableRng};
+
+const MAP_SIZE: u32 = 256;
+const MAP_SEED: u64 = 0x5eed_de7a11;
+/// Lattice cells across the map for each noise octave and its weight.
+const OCTAVES: [(u32, f32); 4] = [(8, 1.0), (16, 0.5), (32, 0.3), (64, 0.15)];
+/// Converts height differences per texel into normal tilt.
+const BUMP: f32 = 6.0;
+const DEFAULT_STRENGTH: f32 = 0.6;
+const DEFAULT_TILING: f32 = 0.35;
+const DEFAULT_MIP_BIAS: f32 = 0.5;
+/// Roughly the far edge of the flat map as the map framing shows it.
+const DEFAULT_FADE_DISTANCE: f32 = 600.0;
+
+/// Fine surface relief blended into the terrain shading normal.
+#[derive(Copy, Clone, Debug)]
+pub struct DetailSettings {
+    /// Scale of the perturbation; 0 shades with the geometric normal only.
+    pub strength: f32,
+    /// Normal-map repeats per world unit.
+    pub tiling: f32,
+    /// Added to the mip level the detail map is sampled at; positive values
+    /// trade sharpness for less shimmer.
+    pub mip_bias: f32,
+    /// Distance from the camera, in world units, by which the biome edges
+    /// have widened fully and the detail normals faded out, so far terrain
+    /// doesn't alias; 0 disables the fade.
+    pub fade_distance: f32,
+}
+
+impl Default for DetailSettings {
+    fn default() -> Self {
+        Self {
+            strength: DEFAULT_STRENGTH,
+            tiling: DEFAULT_TILING,
+            mip_bias: DEFAULT_MIP_BIAS,
+            fade_distance: DEFAULT_FADE_DISTANCE,
+        }
+    }
+}
+
+/// Tileable tangent-space normal map, sampled triplanar by the terrain shader.
+///
+/// The map is generated once at startup from wrapped value noise so no asset
+/// needs to ship with the binary; mips are built on the CPU by averaging and
+/// renormalizing normals.
+pub struct DetailMap {
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+impl DetailMap {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let levels = normal_mips(&height_map(MAP_SIZE));
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("terrain detail normals"),
+            size: wgpu::Extent3d {
+                width: MAP_SIZE,
+                height: MAP_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (level, texels) in levels.iter().enumerate() {
+            let size = MAP_SIZE >> level;
+            let bytes: Vec<u8> = texels.iter().flat_map(|&n| encode(n)).collect();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &bytes,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size * 4),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("terrain detail sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+        }
+    }
+}
+
+/// Sum of wrapped value-noise octaves; every lattice divides `size`, so the
+/// result tiles seamlessly.
+fn height_map(size: u32) -> Vec<f32> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(MAP_SEED);
+    let mut heights = vec![0.0; (size * size) as usize];
+    for (cells, weight) in OCTAVES {
+        let lattice: Vec<f32> = (0..cells * cells).map(|_| rng.gen::<f32>()).collect();
+        let at = |x: u32, y: u32| lattice[((y % cells) * cells + x % cells) as usize];
+        let step = cells as f32 / size as f32;
+        for y in 0..size {
+            for x in 0..size {
+                let fx = x as f32 * step;
+                let fy = y as f32 * step;
+                let (cx, cy) = (fx as u32, fy as u32);
+                let tx = smooth(fx.fract());
+                let ty = smooth(fy.fract());
+                let top = lerp(at(cx, cy), at(cx + 1, cy), tx);
+                let bottom = lerp(at(cx, cy + 1), at(cx + 1, cy + 1), tx);
+                // Folding the noise gives sharper, rock-like creases.
+                let value = (lerp(top, bottom, ty) - 0.5).abs() * 2.0;
+                heights[(y * size + x) as usize] += value * weight;
+            }
+        }
+    }
+    heights
+}
+
+/// Tangent-space normals for the full mip chain, finest level first.
+fn normal_mips(heights: &[f32]) -> Vec<Vec<Vec3>> {
+    let size = MAP_SIZE as usize;
+    let at = |x: usize, y: usize| heights[(y % size) * size + x % size];
+    let base = (0..size * size)
+        .map(|i| {
+            let (x, y) = (i % size, i / size);
+            let dx = at(x + 1, y) - at(x + size - 1, y);
+            let dy = at(x, y + 1) - at(x, y + size - 1);
+            Vec3::new(-dx * BUMP, -dy * BUMP, 1.0).normalize()
+        })
+        .collect();
+
+    let mut levels: Vec<Vec<Vec3>> = vec![base];
+    let mut size = size;
+    while size > 1 {
+        let prev = levels.last().unwrap();
+        let half = size / 2;
+        let next = (0..half * half)
+            .map(|i| {
+                let (x, y) = (i % half * 2, i / half * 2);
+                [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .map(|(ox, oy)| prev[(y + oy) * size + x + ox])
+                    .sum::<Vec3>()
+                    .normalize_or_zero()
+            })
+            .collect();
+        levels.push(next);
+        size = half;
+    }
+    levels
+}
+
+fn encode(n: Vec3) -> [u8; 4] {
+    let q = |c: f32| ((c * 0.5 + 0.5) * 255.0).round() as u8;
+    [q(n.x), q(n.y), q(n.z), 255]
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shader {
+    Terrain,
+    Water,
+    Anaglyph,
+    Weather,
+}
+
+impl Shader {
+    pub const ALL: [Shader; 4] = [
+        Shader::Terrain,
+        Shader::Water,
+        Shader::Anaglyph,
+        Shader::Weather,
+    ];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Shader::Terrain => "terrain.wgsl",
+            Shader::Water => "water.wgsl",
+            Shader::Anaglyph => "anaglyph.wgsl",
+            Shader::Weather => "weather.wgsl",
+        }
+    }
+
+    pub fn path(self) -> PathBuf {
+        Path::new(SHADER_DIR).join(self.file_name())
+    }
+
+    pub fn read(self) -> std::io::Result<String> {
+        std::fs::read_to_string(self.path())
+    }
+
+    fn from_path(path: &Path) -> Option<Shader> {
+        let name = path.file_name()?;
+        Shader::ALL.into_iter().find(|s| name == s.file_name())
+    }
+}
+
+/// Watches the shader directory and reports which shaders changed on disk.
+pub struct ShaderWatcher {
+    // Kept alive for as long as events should be delivered.
+    _watcher: notify::RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(Path::new(SHADER_DIR), RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Shaders touched since the last call, each listed once.
+    pub fn changed(&self) -> Vec<Shader> {
+        let mut changed = Vec::new();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(err) => {
+                    eprintln!("shader watcher: {err}");
+                    continue;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_) | EventKind::Remove(_)) {
+                continue;
+            }
+            for shader in event.paths.iter().filter_map(|p| Shader::from_path(p)) {
+                if !changed.contains(&shader) {
+                    changed.push(shader);
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// Runs `build` inside a validation error scope, returning the error message
+/// instead of letting wgpu's default handler panic.
+pub fn validated<T>(device: &wgpu::Device, build: impl FnOnce() -> T) -> Result<T, String> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = build();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(err) => Err(err.to_string()),
+        None => Ok(value),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::depth;
+
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Id written where nothing was drawn.
+pub const NONE: u32 = 0;
+
+/// Screen-sized `R32Uint` target that objects render their ids into, plus a
+/// one-texel readback buffer.
+///
+/// Only the texel under the cursor is ever needed, so the id pass is recorded
+/// on demand with a 1x1 scissor rather than every frame.
+pub struct IdBuffer {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback: wgpu::Buffer,
+}
+
+impl IdBuffer {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("id buffer"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("id readback"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            texture,
+            view,
+            readback,
+        }
+    }
+
+    /// Begins an id pass restricted to the texel at (`x`, `y`). The depth
+    /// attachment is cleared too, so this must not be interleaved with a
+    /// scene pass that still needs its depth.
+    pub fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        depth_view: &'a wgpu::TextureView,
+        depth_mode: depth::DepthMode,
+        x: u32,
+        y: u32,
+    ) -> wgpu::RenderPass<'a> {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("id pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(depth_mode.clear_value()),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_scissor_rect(x, y, 1, 1);
+        pass
+    }
+
+    pub fn copy_texel(&self, encoder: &mut wgpu::CommandEncoder, x: u32, y: u32) {
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Blocks until the copy recorded by `copy_texel` has landed and returns
+    /// the id, or `None` for background.
+    pub fn read_texel(&self, device: &wgpu::Device) -> Option<u32> {
+        let slice = self.readback.slice(..4);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+        let id = u32::from_ne_bytes(slice.get_mapped_range()[..4].try_into().unwrap());
+        self.readback.unmap();
+        (id != NONE).then_some(id)
+    }
+}
@@ -0,0 +1,41 @@
+/// wgpu has no runtime version constant; keep in sync with Cargo.toml.
+pub const WGPU_VERSION: &str = "0.19";
+
+/// Environment details worth including in bug reports.
+pub struct SystemInfo {
+    adapter: wgpu::AdapterInfo,
+}
+
+impl SystemInfo {
+    pub fn new(adapter: wgpu::AdapterInfo) -> Self {
+        Self { adapter }
+    }
+
+    /// Label/value rows for the adapter and the current surface configuration.
+    pub fn rows(&self, config: &wgpu::SurfaceConfiguration) -> Vec<(&'static str, String)> {
+        let adapter = &self.adapter;
+        vec![
+            ("Adapter", adapter.name.clone()),
+            ("Backend", format!("{:?}", adapter.backend)),
+            ("Device type", format!("{:?}", adapter.device_type)),
+            (
+                "Driver",
+                format!("{} {}", adapter.driver, adapter.driver_info)
+                    .trim()
+                    .to_string(),
+            ),
+            ("Surface format", format!("{:?}", config.format)),
+            ("Present mode", format!("{:?}", config.present_mode)),
+            ("Resolution", format!("{}x{}", config.width, config.height)),
+            ("wgpu", WGPU_VERSION.to_string()),
+        ]
+    }
+
+    pub fn report(&self, config: &wgpu::SurfaceConfiguration) -> String {
+        self.rows(config)
+            .into_iter()
+            .map(|(label, value)| format!("{label}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -2,84 +2,290 @@ use glam::{Vec2, Vec3};
 use winit::event::{ElementState, KeyEvent, MouseScrollDelta};
 use winit::keyboard::{KeyCode, PhysicalKey};
 
-use crate::terrain::{HEIGHT_AMPLITUDE, WORLD_RADIUS};
+use crate::world::WorldConfig;
 
 const MIN_ORBIT_FACTOR: f32 = 1.2;
 const MAX_ORBIT_FACTOR: f32 = 8.0;
 const ZOOM_SENSITIVITY: f32 = 0.2;
+const MAX_PITCH: f32 = 1.4;
+
+/// Initial camera pose. The camera orbits `target` at the distance of
+/// `position` from it; zoom limits still apply to that distance.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CameraStart {
+    /// `None` starts above the northern hemisphere, at a distance scaled to
+    /// the globe's radius.
+    pub position: Option<Vec3>,
+    pub target: Vec3,
+}
+
+/// Orbit camera parameters, as animated by `InputState::reframe`.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraPose {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub orbit_radius: f32,
+    pub target: Vec3,
+}
 
 pub struct InputState {
     pub position: Vec3,
+    pub target: Vec3,
     yaw: f32,
     pitch: f32,
     orbit_radius: f32,
     orbit_speed: f32,
+    /// Globe radius the zoom limits are measured from.
+    world_radius: f32,
+    /// Terrain height scale; the closest zoom keeps a multiple of it above
+    /// the globe.
+    height_amplitude: f32,
+    /// Pose being eased towards and the easing rate, until reached or
+    /// interrupted by manual camera input.
+    camera_goal: Option<(CameraPose, f32)>,
     shift: bool,
+    ctrl: bool,
     pub active: bool,
     w: bool,
     a: bool,
     s: bool,
     d: bool,
+    /// Held sun nudges: `[` and `]` turn it west and east, `;` and `'`
+    /// lower and raise it.
+    sun_west: bool,
+    sun_east: bool,
+    sun_lower: bool,
+    sun_raise: bool,
     randomize: bool,
     toggle_map: bool,
+    toggle_stereo: bool,
+    cycle_brush: bool,
+    cycle_depth_mode: bool,
+    identify: bool,
+    smooth: bool,
+    toggle_measure: bool,
+    screenshot: bool,
+    toggle_time_pause: bool,
+    step_time: bool,
+    ui_scale_step: i32,
+    ui_scale_reset: bool,
+    undo: bool,
+    redo: bool,
+    stroke_active: bool,
+    stroke_began: bool,
+    stroke_ended: bool,
     last_cursor: Option<Vec2>,
     sensitivity: f32,
     release_cursor: bool,
 }
 
 impl InputState {
-    pub fn new(speed: f32) -> Self {
-        let position = Vec3::new(0.0, WORLD_RADIUS * 0.8, WORLD_RADIUS * 1.8);
-        let forward_to_origin = (Vec3::ZERO - position).normalize();
-        let yaw = forward_to_origin.x.atan2(forward_to_origin.z);
-        let pitch = forward_to_origin.y.asin();
+    pub fn new(speed: f32, start: CameraStart, world: &WorldConfig) -> Self {
+        let world_radius = world.radius;
+        let overview = Vec3::new(0.0, world_radius * 0.8, world_radius * 1.8);
+        let position = start.position.unwrap_or(overview);
+        let offset = position - start.target;
+        let forward = (-offset).try_normalize().unwrap_or(Vec3::NEG_Z);
+        let yaw = forward.x.atan2(forward.z);
+        let pitch = forward.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
 
-        Self {
+        let mut state = Self {
             position,
+            target: start.target,
             yaw,
             pitch,
-            orbit_radius: position.length(),
+            orbit_radius: offset.length(),
             orbit_speed: speed,
+            world_radius,
+            height_amplitude: world.height_amplitude,
+            camera_goal: None,
             shift: false,
+            ctrl: false,
             active: true,
             w: false,
             a: false,
             s: false,
             d: false,
+            sun_west: false,
+            sun_east: false,
+            sun_lower: false,
+            sun_raise: false,
             randomize: false,
             toggle_map: false,
+            toggle_stereo: false,
+            cycle_brush: false,
+            cycle_depth_mode: false,
+            identify: false,
+            smooth: false,
+            toggle_measure: false,
+            screenshot: false,
+            toggle_time_pause: false,
+            step_time: false,
+            ui_scale_step: 0,
+            ui_scale_reset: false,
+            undo: false,
+            redo: false,
+            stroke_active: false,
+            stroke_began: false,
+            stroke_ended: false,
             last_cursor: None,
             sensitivity: 0.0025,
             release_cursor: false,
+        };
+        state.orbit_radius = state.clamp_orbit(state.orbit_radius);
+        state.position = state.orbit_position();
+        state
+    }
+
+    fn clamp_orbit(&self, radius: f32) -> f32 {
+        let min = self.world_radius + self.height_amplitude * MIN_ORBIT_FACTOR;
+        radius.clamp(min, self.world_radius * MAX_ORBIT_FACTOR)
+    }
+
+    fn orbit_position(&self) -> Vec3 {
+        self.target - self.forward() * self.orbit_radius
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            yaw: self.yaw,
+            pitch: self.pitch,
+            orbit_radius: self.orbit_radius,
+            target: self.target,
         }
     }
 
+    /// Eases the camera to `goal`, covering `1 - exp(-speed * dt)` of the
+    /// remaining way each frame. Any manual orbit or zoom cancels it.
+    pub fn reframe(&mut self, goal: CameraPose, speed: f32) {
+        let goal = CameraPose {
+            pitch: goal.pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            orbit_radius: self.clamp_orbit(goal.orbit_radius),
+            ..goal
+        };
+        self.camera_goal = Some((goal, speed));
+    }
+
+    pub fn reframing(&self) -> bool {
+        self.camera_goal.is_some()
+    }
+
+    fn step_reframe(&mut self, dt: f32) {
+        let Some((goal, speed)) = self.camera_goal else {
+            return;
+        };
+        let step = 1.0 - (-speed * dt).exp();
+        let yaw_delta = (goal.yaw - self.yaw + std::f32::consts::PI)
+            .rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+        self.yaw += yaw_delta * step;
+        self.pitch += (goal.pitch - self.pitch) * step;
+        self.orbit_radius += (goal.orbit_radius - self.orbit_radius) * step;
+        self.target = self.target.lerp(goal.target, step);
+
+        let settled = yaw_delta.abs() < 1e-3
+            && (goal.pitch - self.pitch).abs() < 1e-3
+            && (goal.orbit_radius - self.orbit_radius).abs() < 1e-2
+            && self.target.distance(goal.target) < 1e-2;
+        if settled {
+            self.yaw = goal.yaw;
+            self.pitch = goal.pitch;
+            self.orbit_radius = goal.orbit_radius;
+            self.target = goal.target;
+            self.camera_goal = None;
+        }
+        self.position = self.orbit_position();
+    }
+
     pub fn handle_key(&mut self, event: &KeyEvent) -> bool {
         let pressed = matches!(event.state, ElementState::Pressed);
         match event.physical_key {
             PhysicalKey::Code(KeyCode::KeyW) => {
                 self.w = pressed;
                 self.active = true;
+                if pressed {
+                    self.camera_goal = None;
+                }
             }
             PhysicalKey::Code(KeyCode::KeyA) => {
                 self.a = pressed;
                 self.active = true;
+                if pressed {
+                    self.camera_goal = None;
+                }
             }
             PhysicalKey::Code(KeyCode::KeyS) => {
                 self.s = pressed;
                 self.active = true;
+                if pressed {
+                    self.camera_goal = None;
+                }
             }
             PhysicalKey::Code(KeyCode::KeyD) => {
                 self.d = pressed;
                 self.active = true;
+                if pressed {
+                    self.camera_goal = None;
+                }
             }
+            PhysicalKey::Code(KeyCode::BracketLeft) => self.sun_west = pressed,
+            PhysicalKey::Code(KeyCode::BracketRight) => self.sun_east = pressed,
+            PhysicalKey::Code(KeyCode::Semicolon) => self.sun_lower = pressed,
+            PhysicalKey::Code(KeyCode::Quote) => self.sun_raise = pressed,
             PhysicalKey::Code(KeyCode::ShiftLeft | KeyCode::ShiftRight) => self.shift = pressed,
+            PhysicalKey::Code(KeyCode::ControlLeft | KeyCode::ControlRight) => self.ctrl = pressed,
+            PhysicalKey::Code(KeyCode::KeyZ) if pressed && self.ctrl => {
+                if self.shift {
+                    self.redo = true;
+                } else {
+                    self.undo = true;
+                }
+            }
+            PhysicalKey::Code(KeyCode::KeyY) if pressed && self.ctrl => {
+                self.redo = true;
+            }
+            PhysicalKey::Code(KeyCode::Equal | KeyCode::NumpadAdd) if pressed && self.ctrl => {
+                self.ui_scale_step += 1;
+            }
+            PhysicalKey::Code(KeyCode::Minus | KeyCode::NumpadSubtract) if pressed && self.ctrl => {
+                self.ui_scale_step -= 1;
+            }
+            PhysicalKey::Code(KeyCode::Digit0 | KeyCode::Numpad0) if pressed && self.ctrl => {
+                self.ui_scale_reset = true;
+            }
             PhysicalKey::Code(KeyCode::KeyR) if pressed => {
                 self.randomize = true;
             }
             PhysicalKey::Code(KeyCode::KeyM) if pressed => {
                 self.toggle_map = true;
             }
+            PhysicalKey::Code(KeyCode::KeyV) if pressed => {
+                self.toggle_stereo = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyB) if pressed => {
+                self.cycle_brush = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyL) if pressed => {
+                self.cycle_depth_mode = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyI) if pressed => {
+                self.identify = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyK) if pressed => {
+                self.smooth = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyT) if pressed => {
+                self.toggle_measure = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyP) if pressed => {
+                self.screenshot = true;
+            }
+            PhysicalKey::Code(KeyCode::Space) if pressed => {
+                self.toggle_time_pause = true;
+            }
+            PhysicalKey::Code(KeyCode::Period) if pressed => {
+                self.step_time = true;
+            }
             PhysicalKey::Code(KeyCode::Escape) if pressed => {
                 self.deactivate();
                 self.release_cursor = true;
@@ -101,11 +307,47 @@ impl InputState {
         self.a = false;
         self.s = false;
         self.d = false;
+        self.sun_west = false;
+        self.sun_east = false;
+        self.sun_lower = false;
+        self.sun_raise = false;
         self.shift = false;
+        self.ctrl = false;
         self.last_cursor = None;
     }
 
+    /// Starts a brush stroke. The camera stays still until `end_stroke`.
+    pub fn begin_stroke(&mut self) {
+        self.deactivate();
+        self.stroke_active = true;
+        self.stroke_began = true;
+    }
+
+    pub fn end_stroke(&mut self) {
+        if self.stroke_active {
+            self.stroke_active = false;
+            self.stroke_ended = true;
+        }
+    }
+
+    pub fn stroke_active(&self) -> bool {
+        self.stroke_active
+    }
+
+    pub fn take_stroke_begin(&mut self) -> bool {
+        let b = self.stroke_began;
+        self.stroke_began = false;
+        b
+    }
+
+    pub fn take_stroke_end(&mut self) -> bool {
+        let e = self.stroke_ended;
+        self.stroke_ended = false;
+        e
+    }
+
     pub fn update(&mut self, dt: f32) {
+        self.step_reframe(dt);
         if !self.active {
             return;
         }
@@ -132,10 +374,10 @@ impl InputState {
                 self.orbit_speed
             };
             self.yaw += yaw_delta * speed * dt;
-            self.pitch = (self.pitch + pitch_delta * speed * dt).clamp(-1.4, 1.4);
+            self.pitch = (self.pitch + pitch_delta * speed * dt).clamp(-MAX_PITCH, MAX_PITCH);
         }
 
-        self.position = -self.forward() * self.orbit_radius;
+        self.position = self.orbit_position();
     }
 
     pub fn handle_scroll(&mut self, delta: &MouseScrollDelta) -> bool {
@@ -147,11 +389,10 @@ impl InputState {
             return false;
         }
 
-        let min_orbit = WORLD_RADIUS + HEIGHT_AMPLITUDE * MIN_ORBIT_FACTOR;
-        let max_orbit = WORLD_RADIUS * MAX_ORBIT_FACTOR;
+        self.camera_goal = None;
         let factor = (1.0 - scroll * ZOOM_SENSITIVITY).clamp(0.5, 1.5);
-        self.orbit_radius = (self.orbit_radius * factor).clamp(min_orbit, max_orbit);
-        self.position = -self.forward() * self.orbit_radius;
+        self.orbit_radius = self.clamp_orbit(self.orbit_radius * factor);
+        self.position = self.orbit_position();
         true
     }
 
@@ -162,8 +403,11 @@ impl InputState {
         }
         if let Some(last) = self.last_cursor {
             let delta = pos - last;
+            if delta != Vec2::ZERO {
+                self.camera_goal = None;
+            }
             self.yaw -= delta.x * self.sensitivity;
-            self.pitch = (self.pitch - delta.y * self.sensitivity).clamp(-1.4, 1.4);
+            self.pitch = (self.pitch - delta.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
         }
         self.last_cursor = Some(pos);
     }
@@ -172,13 +416,33 @@ impl InputState {
         if !self.active {
             return;
         }
+        self.camera_goal = None;
         self.yaw -= delta.0 as f32 * self.sensitivity;
-        self.pitch = (self.pitch - delta.1 as f32 * self.sensitivity).clamp(-1.4, 1.4);
+        self.pitch = (self.pitch - delta.1 as f32 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
     }
 
     pub fn forward(&self) -> Vec3 {
-        let cp = self.pitch.cos();
-        Vec3::new(self.yaw.sin() * cp, self.pitch.sin(), self.yaw.cos() * cp).normalize()
+        orbit_forward(self.yaw, self.pitch)
+    }
+
+    /// Directions the held sun keys turn it, azimuth then elevation, each
+    /// -1, 0 or 1.
+    pub fn sun_nudge(&self) -> (f32, f32) {
+        let mut azimuth = 0.0;
+        let mut elevation = 0.0;
+        if self.sun_east {
+            azimuth += 1.0;
+        }
+        if self.sun_west {
+            azimuth -= 1.0;
+        }
+        if self.sun_raise {
+            elevation += 1.0;
+        }
+        if self.sun_lower {
+            elevation -= 1.0;
+        }
+        (azimuth, elevation)
     }
 
     pub fn take_randomize(&mut self) -> bool {
@@ -192,4 +456,90 @@ impl InputState {
         self.toggle_map = false;
         t
     }
+
+    pub fn take_toggle_stereo(&mut self) -> bool {
+        let t = self.toggle_stereo;
+        self.toggle_stereo = false;
+        t
+    }
+
+    pub fn take_cycle_brush(&mut self) -> bool {
+        let t = self.cycle_brush;
+        self.cycle_brush = false;
+        t
+    }
+
+    pub fn take_cycle_depth_mode(&mut self) -> bool {
+        let t = self.cycle_depth_mode;
+        self.cycle_depth_mode = false;
+        t
+    }
+
+    pub fn take_undo(&mut self) -> bool {
+        let u = self.undo;
+        self.undo = false;
+        u
+    }
+
+    pub fn take_redo(&mut self) -> bool {
+        let r = self.redo;
+        self.redo = false;
+        r
+    }
+
+    pub fn take_identify(&mut self) -> bool {
+        let i = self.identify;
+        self.identify = false;
+        i
+    }
+
+    pub fn take_smooth(&mut self) -> bool {
+        let s = self.smooth;
+        self.smooth = false;
+        s
+    }
+
+    pub fn take_toggle_measure(&mut self) -> bool {
+        let t = self.toggle_measure;
+        self.toggle_measure = false;
+        t
+    }
+
+    pub fn take_screenshot(&mut self) -> bool {
+        let s = self.screenshot;
+        self.screenshot = false;
+        s
+    }
+
+    pub fn take_toggle_time_pause(&mut self) -> bool {
+        let t = self.toggle_time_pause;
+        self.toggle_time_pause = false;
+        t
+    }
+
+    pub fn take_step_time(&mut self) -> bool {
+        let s = self.step_time;
+        self.step_time = false;
+        s
+    }
+
+    /// Net Ctrl+= / Ctrl+- presses since the last call.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn take_ui_scale_step(&mut self) -> i32 {
+        std::mem::take(&mut self.ui_scale_step)
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn take_ui_scale_reset(&mut self) -> bool {
+        let r = self.ui_scale_reset;
+        self.ui_scale_reset = false;
+        r
+    }
+}
+
+/// View direction of an orbit camera at `yaw` about +Y and `pitch` above the
+/// horizon; the camera sits at `target - forward * orbit_radius`.
+pub fn orbit_forward(yaw: f32, pitch: f32) -> Vec3 {
+    let cp = pitch.cos();
+    Vec3::new(yaw.sin() * cp, pitch.sin(), yaw.cos() * cp).normalize()
 }
@@ -0,0 +1,145 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+/// Progress counter shared between a background job and its owner.
+#[derive(Clone)]
+pub struct Progress {
+    done: Arc<AtomicU32>,
+    total: u32,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Progress {
+    /// A counter for `total` steps, also usable for work run in place.
+    pub fn new(total: u32) -> Self {
+        Self {
+            done: Arc::new(AtomicU32::new(0)),
+            total,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn advance(&self, steps: u32) {
+        self.done.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    /// Completed share of the work, in [0, 1].
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn fraction(&self) -> f32 {
+        let done = self.done.load(Ordering::Relaxed);
+        (done as f32 / self.total.max(1) as f32).min(1.0)
+    }
+
+    /// Set once the owner has dropped the job; work should stop early, as
+    /// its result will be discarded.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+pub enum JobStatus<T> {
+    Running,
+    Done(T),
+    /// The worker panicked; its message has already gone to stderr.
+    Failed,
+}
+
+/// Work running on its own thread, polled from the frame loop so the window
+/// keeps drawing meanwhile. Dropping the job cancels it.
+pub struct BackgroundJob<T> {
+    progress: Progress,
+    receiver: Receiver<T>,
+}
+
+impl<T: Send + 'static> BackgroundJob<T> {
+    /// Starts `work` on a named thread. It reports through the `Progress`
+    /// it is handed, out of `total` steps.
+    pub fn spawn(
+        name: &str,
+        total: u32,
+        work: impl FnOnce(&Progress) -> T + Send + 'static,
+    ) -> Self {
+        let progress = Progress::new(total);
+        let (sender, receiver) = mpsc::channel();
+        let worker = progress.clone();
+        thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || {
+                // The owner may be gone already; the result is simply dropped.
+                let _ = sender.send(work(&worker));
+            })
+            .expect("failed to spawn background job thread");
+        Self { progress, receiver }
+    }
+
+    pub fn poll(&self) -> JobStatus<T> {
+        match self.receiver.try_recv() {
+            Ok(result) => JobStatus::Done(result),
+            Err(TryRecvError::Empty) => JobStatus::Running,
+            Err(TryRecvError::Disconnected) => JobStatus::Failed,
+        }
+    }
+}
+
+impl<T> BackgroundJob<T> {
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+}
+
+impl<T> Drop for BackgroundJob<T> {
+    fn drop(&mut self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Polls until the job stops running.
+    fn finish<T: Send + 'static>(job: &BackgroundJob<T>) -> JobStatus<T> {
+        loop {
+            match job.poll() {
+                JobStatus::Running => thread::sleep(Duration::from_millis(1)),
+                status => return status,
+            }
+        }
+    }
+
+    #[test]
+    fn finished_work_comes_back_with_its_progress() {
+        let job = BackgroundJob::spawn("test job", 4, |progress| {
+            progress.advance(4);
+            6 * 7
+        });
+        assert!(matches!(finish(&job), JobStatus::Done(42)));
+        assert_eq!(job.progress().fraction(), 1.0);
+        assert!(!job.progress().is_cancelled());
+    }
+
+    #[test]
+    fn dropping_the_job_cancels_its_work() {
+        let (stopped, seen) = mpsc::channel();
+        let job = BackgroundJob::spawn("test job", 1, move |progress| {
+            while !progress.is_cancelled() {
+                thread::yield_now();
+            }
+            stopped.send(()).unwrap();
+        });
+        let progress = job.progress().clone();
+        assert!(!progress.is_cancelled());
+        drop(job);
+        assert!(progress.is_cancelled());
+        seen.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+
+    #[test]
+    fn a_panicking_worker_fails_the_job() {
+        let job = BackgroundJob::spawn("test job", 1, |_| -> u32 { panic!("worker gave up") });
+        assert!(matches!(finish(&job), JobStatus::Failed));
+    }
+}
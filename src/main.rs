@@ -1,50 +1,163 @@
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec3, Vec4};
 use rand::{rngs::StdRng, SeedableRng};
 use std::error::Error;
+use std::path::Path;
 use std::time::Instant;
 use wgpu::{SurfaceError, SurfaceTargetUnsafe};
 
+mod biome;
+mod brush;
 mod input;
 mod depth;
+mod detail;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+mod id_buffer;
+mod info;
+mod jobs;
+mod measure;
+mod mesh;
+mod pacing;
+mod picking;
+mod screenshot;
+mod session;
+mod stereo;
+mod sun;
 mod terrain;
+mod transition;
+mod turntable;
+mod undo;
+mod view;
+#[cfg(feature = "ui")]
+mod viewport;
 mod water;
+mod weather;
+mod world;
 use winit::{
     dpi::PhysicalSize,
     event::{DeviceEvent, Event, MouseButton, WindowEvent},
     event_loop::EventLoop,
+    platform::run_on_demand::EventLoopExtRunOnDemand,
     window::{CursorGrabMode, Window, WindowBuilder},
 };
 
-const MAP_TRANSITION_SPEED: f32 = 2.5;
+const UNDO_LIMIT: usize = 32;
+/// Animation time added by one step while time is paused.
+const TIME_STEP: f32 = 1.0 / 60.0;
+#[cfg(feature = "ui")]
+const DEFAULT_UI_SCALE: f32 = 1.0;
+#[cfg(feature = "ui")]
+const MIN_UI_SCALE: f32 = 0.75;
+#[cfg(feature = "ui")]
+const MAX_UI_SCALE: f32 = 2.0;
+#[cfg(feature = "ui")]
+const UI_SCALE_STEP: f32 = 0.1;
+#[cfg(feature = "ui")]
+const DEFAULT_EXPORT_PATH: &str = "terrain.csv";
 
 #[cfg(feature = "ui")]
 use egui_wgpu::ScreenDescriptor;
 
-struct State {
+/// The window a `State` presents to. Fields drop in declaration order:
+/// `surface` must stay ahead of `window`, whose raw handle it was created from.
+struct Presenter {
     surface: wgpu::Surface<'static>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    info: info::SystemInfo,
+    #[cfg(feature = "ui")]
+    gui: Gui,
     window: Window,
+}
+
+/// wgpu resources hold their own reference to the device, so apart from
+/// `Presenter` the fields may drop in any order.
+struct State {
+    /// `None` for a headless state, which only draws through `render_to`.
+    presenter: Option<Presenter>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// Size and format of the scene's color target: the window surface, the
+    /// embedded viewport image when there is one, or a headless host's target.
+    scene_config: wgpu::SurfaceConfiguration,
     size: PhysicalSize<u32>,
-    clear: Vec3,
+    /// Background color. Fully transparent (and black, as premultiplied
+    /// surfaces require) when the window is composited over the desktop.
+    clear: Vec4,
     depth: depth::DepthTexture,
+    ids: id_buffer::IdBuffer,
     input: input::InputState,
+    /// Grid, globe and camera tunables the terrain and water were built with.
+    world: world::WorldConfig,
     map_blend: f32,
     map_target: f32,
+    map_transition: transition::MapTransition,
     map_rotation: f32,
+    map_auto_center: bool,
+    /// Ease the camera to a whole-map framing when flattening, and back to
+    /// `globe_pose` when returning.
+    map_reframe: bool,
+    globe_pose: Option<input::CameraPose>,
     last_frame: Instant,
+    /// Animation clock in seconds.
+    time: f32,
+    /// Hold the animation clock; the camera and map transition keep going.
+    time_paused: bool,
+    /// Latched by a step request: advance the paused clock by `TIME_STEP`
+    /// on the next tick.
+    time_step: bool,
+    pacing: pacing::FramePacing,
     rng: StdRng,
-    terrain: terrain::Terrain,
+    stereo: stereo::StereoSettings,
+    anaglyph: stereo::Anaglyph,
+    sun: sun::SunSettings,
+    brush: brush::BrushSettings,
+    paint_history: undo::UndoStack<Vec<[f32; 4]>>,
+    measure: measure::MeasureSettings,
+    measurement: measure::Measurement,
+    cursor: glam::Vec2,
+    /// Cursor pixel of the last identify (I) press and the object id
+    /// `pick_at` found there, for the overlay.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    identified: Option<([u32; 2], Option<u32>)>,
+    view_proj: Mat4,
+    depth_mode: depth::DepthMode,
+    /// Skip the far side of the globe when drawing; see `SceneView`.
+    horizon_cull: bool,
+    terrain_renderer: terrain::TerrainRenderer,
+    /// Never empty. The first is the base terrain: it keeps the identity
+    /// transform, and the water, weather, measuring and map framing follow
+    /// it. Later ones are tiles or inserts drawn alongside.
+    terrains: Vec<terrain::Terrain>,
+    /// Index into `terrains` of the one the overlay, brush and key actions
+    /// edit.
+    selected: usize,
     water: water::Water,
+    water_revision: u64,
+    weather: weather::Weather,
+    /// Frames still to render, one per tick, when a turntable is running.
+    turntable: Option<turntable::Turntable>,
+    #[cfg(feature = "hot-reload")]
+    shader_watcher: Option<hot_reload::ShaderWatcher>,
+    /// Overlay size relative to the window's reported scale factor.
     #[cfg(feature = "ui")]
-    gui: Gui,
+    ui_scale: f32,
+    /// What "Reset to defaults" restores.
+    #[cfg(feature = "ui")]
+    defaults: Defaults,
+    /// Set when the scene is embedded as an image in the egui layout.
+    #[cfg(feature = "ui")]
+    viewport: Option<viewport::SceneViewport>,
 }
 
 impl State {
-    async fn new(event_loop: &EventLoop<()>) -> Result<Self, Box<dyn Error>> {
+    async fn new(
+        event_loop: &EventLoop<()>,
+        options: LaunchOptions,
+    ) -> Result<Self, Box<dyn Error>> {
         let window = WindowBuilder::new()
             .with_title("wgpu + winit bootstrap")
+            .with_transparent(options.transparent)
             .build(event_loop)?;
 
         let size = window.inner_size();
@@ -84,97 +197,530 @@ impl State {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
+        // Only these modes let the compositor see the cleared alpha.
+        let blended = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ];
+        let alpha_mode = surface_caps
+            .alpha_modes
+            .iter()
+            .copied()
+            .find(|mode| options.transparent && blended.contains(mode));
+        if options.transparent && alpha_mode.is_none() {
+            eprintln!(
+                "transparent window not supported (alpha modes: {:?}); falling back to opaque",
+                surface_caps.alpha_modes
+            );
+        }
+        let clear = if alpha_mode.is_some() {
+            Vec4::ZERO
+        } else {
+            Vec4::new(0.05, 0.08, 0.1, 1.0)
+        };
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width.max(1),
             height: size.height.max(1),
             present_mode: wgpu::PresentMode::Fifo,
-            desired_maximum_frame_latency: 2,
-            alpha_mode: surface_caps.alpha_modes[0],
+            desired_maximum_frame_latency: options.pacing.frame_latency(),
+            alpha_mode: alpha_mode.unwrap_or(surface_caps.alpha_modes[0]),
             view_formats: vec![],
         };
         surface.configure(&device, &config);
+        let info = info::SystemInfo::new(adapter.get_info());
+        if options.info {
+            println!("{}", info.report(&config));
+        }
 
+        #[cfg(feature = "ui")]
+        let embedded = options.embedded;
+        let mut state = Self::headless(device, queue, surface_format, size, options);
+        state.clear = clear;
+        #[cfg(feature = "ui")]
+        let mut gui = Gui::new(&window, &state.device, surface_format);
+        // Starts at the window size and follows the image once it is laid out.
+        #[cfg(feature = "ui")]
+        if embedded {
+            let size = [config.width, config.height];
+            state.viewport = Some(viewport::SceneViewport::new(
+                &state.device,
+                &mut gui.renderer,
+                surface_format,
+                size,
+            ));
+        }
+        state.presenter = Some(Presenter {
+            surface,
+            config,
+            info,
+            #[cfg(feature = "ui")]
+            gui,
+            window,
+        });
+        Ok(state)
+    }
+
+    /// Builds the scene on an existing device, drawing `format` targets of
+    /// `size`, with no window or surface: a host drives it with `tick` and
+    /// `render_to`. `new` builds the windowed state through this too.
+    pub fn headless(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+        options: LaunchOptions,
+    ) -> Self {
+        let scene_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: options.pacing.frame_latency(),
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let defaults = Defaults::new(&options);
         let mut rng = StdRng::from_entropy();
-        let terrain = terrain::Terrain::new(&device, surface_format, &mut rng);
-        let depth = depth::DepthTexture::new(&device, &config);
-        let water = water::Water::new(&device, surface_format, terrain::WATER_LEVEL);
+        let terrain_renderer = terrain::TerrainRenderer::new(&device, &queue, format);
+        let mut terrain =
+            terrain::Terrain::new(&device, &terrain_renderer, &options.world, &mut rng);
+        terrain.set_double_buffered(&device, defaults.double_buffer);
+        let depth = depth::DepthTexture::new(&device, &scene_config);
+        let ids = id_buffer::IdBuffer::new(&device, &scene_config);
+        let water = water::Water::new(&device, format, terrain.water_level(), &options.world);
+        water.set_heightfield(&queue, terrain.heights());
+        let water_revision = terrain.revision();
+        let weather = weather::Weather::new(&device, format);
+        let anaglyph = stereo::Anaglyph::new(&device, &scene_config);
 
-        #[cfg(feature = "ui")]
-        let gui = Gui::new(&window, &device, surface_format);
+        #[cfg(feature = "hot-reload")]
+        let shader_watcher = hot_reload::ShaderWatcher::new()
+            .map_err(|err| eprintln!("shader hot reload disabled: {err}"))
+            .ok();
 
-        Ok(Self {
-            surface,
+        #[allow(unused_mut)]
+        let mut state = Self {
+            presenter: None,
             device,
             queue,
-            config,
-            window,
+            scene_config,
             size,
-            clear: Vec3::new(0.05, 0.08, 0.1),
+            clear: Vec4::new(0.05, 0.08, 0.1, 1.0),
             depth,
-            input: input::InputState::new(1.2),
+            ids,
+            input: input::InputState::new(1.2, options.camera, &options.world),
+            world: options.world,
             map_blend: 0.0,
             map_target: 0.0,
+            map_transition: defaults.map_transition,
             map_rotation: 0.0,
+            map_auto_center: defaults.map_auto_center,
+            map_reframe: defaults.map_reframe,
+            globe_pose: None,
             last_frame: Instant::now(),
+            time: 0.0,
+            time_paused: false,
+            time_step: false,
+            pacing: defaults.pacing,
             rng,
-            terrain,
+            stereo: defaults.stereo,
+            anaglyph,
+            sun: defaults.sun,
+            brush: defaults.brush,
+            paint_history: undo::UndoStack::new(UNDO_LIMIT),
+            measure: defaults.measure,
+            measurement: measure::Measurement::default(),
+            cursor: glam::Vec2::ZERO,
+            identified: None,
+            view_proj: Mat4::IDENTITY,
+            depth_mode: defaults.depth_mode,
+            horizon_cull: defaults.horizon_cull,
+            terrain_renderer,
+            terrains: vec![terrain],
+            selected: 0,
             water,
+            water_revision,
+            weather,
+            turntable: None,
+            #[cfg(feature = "hot-reload")]
+            shader_watcher,
             #[cfg(feature = "ui")]
-            gui,
-        })
+            ui_scale: defaults.ui_scale,
+            #[cfg(feature = "ui")]
+            defaults,
+            #[cfg(feature = "ui")]
+            viewport: None,
+        };
+        // Prefer the on-disk shaders over the baked-in copies from the start.
+        #[cfg(feature = "hot-reload")]
+        state.reload_shaders(&hot_reload::Shader::ALL);
+        if let Some(session) = options.session {
+            if let Err(err) = state.apply_session(session) {
+                eprintln!("session: {err}");
+            }
+        }
+        if let Some(settings) = options.turntable {
+            state.start_turntable(settings, turntable::DEFAULT_DIRECTORY.as_ref());
+        }
+        state
+    }
+
+    /// Recompiles the given shaders from disk. A shader that fails to read,
+    /// compile or validate keeps its last good pipeline.
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&mut self, shaders: &[hot_reload::Shader]) {
+        for &shader in shaders {
+            let path = shader.path();
+            let source = match shader.read() {
+                Ok(source) => source,
+                Err(err) => {
+                    eprintln!("{}: {err}", path.display());
+                    continue;
+                }
+            };
+            let result = match shader {
+                hot_reload::Shader::Terrain => {
+                    self.terrain_renderer.reload_shader(&self.device, &source)
+                }
+                hot_reload::Shader::Water => self.water.reload_shader(&self.device, &source),
+                hot_reload::Shader::Anaglyph => self.anaglyph.reload_shader(&self.device, &source),
+                hot_reload::Shader::Weather => self.weather.reload_shader(&self.device, &source),
+            };
+            match result {
+                Ok(()) => println!("loaded {}", path.display()),
+                Err(err) => eprintln!("{}: {err}\nkeeping the previous pipeline", path.display()),
+            }
+        }
     }
 
-    fn window(&self) -> &Window {
-        &self.window
+    fn window(&self) -> Option<&Window> {
+        self.presenter.as_ref().map(|presenter| &presenter.window)
     }
 
     fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth = depth::DepthTexture::new(&self.device, &self.config);
+            if let Some(presenter) = &mut self.presenter {
+                presenter.config.width = new_size.width;
+                presenter.config.height = new_size.height;
+                presenter.surface.configure(&self.device, &presenter.config);
+            }
+            if !self.embedded() {
+                self.resize_scene(new_size.width, new_size.height);
+            }
+        }
+    }
+
+    /// Resizes the scene's depth, id and stereo targets.
+    fn resize_scene(&mut self, width: u32, height: u32) {
+        self.scene_config.width = width;
+        self.scene_config.height = height;
+        self.depth = depth::DepthTexture::new(&self.device, &self.scene_config);
+        self.ids = id_buffer::IdBuffer::new(&self.device, &self.scene_config);
+        self.anaglyph.resize(&self.device, &self.scene_config);
+    }
+
+    /// Takes the scene image's new placement, resizing the scene targets to
+    /// match it.
+    #[cfg(feature = "ui")]
+    fn set_viewport_layout(&mut self, layout: viewport::ViewportLayout) {
+        let (Some(viewport), Some(presenter)) = (&mut self.viewport, &mut self.presenter) else {
+            return;
+        };
+        viewport.layout = layout;
+        viewport.resize(&self.device, &mut presenter.gui.renderer, layout.size);
+        if [self.scene_config.width, self.scene_config.height] != layout.size {
+            self.resize_scene(layout.size[0], layout.size[1]);
+        }
+    }
+
+    fn embedded(&self) -> bool {
+        #[cfg(feature = "ui")]
+        return self.viewport.is_some();
+        #[cfg(not(feature = "ui"))]
+        return false;
+    }
+
+    /// Converts a window cursor position to scene-target pixels.
+    fn scene_cursor(&self, position: glam::Vec2) -> glam::Vec2 {
+        #[cfg(feature = "ui")]
+        if let Some(viewport) = &self.viewport {
+            return position - viewport.layout.origin;
+        }
+        position
+    }
+
+    /// Applies new pacing; a latency change reconfigures the surface.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    fn set_pacing(&mut self, pacing: pacing::FramePacing) {
+        if let Some(presenter) = &mut self.presenter {
+            let config = &mut presenter.config;
+            if pacing.frame_latency() != config.desired_maximum_frame_latency {
+                config.desired_maximum_frame_latency = pacing.frame_latency();
+                presenter.surface.configure(&self.device, config);
+            }
+        }
+        self.pacing = pacing;
+    }
+
+    /// The terrain the overlay, brush and key actions edit.
+    fn terrain(&self) -> &terrain::Terrain {
+        &self.terrains[self.selected]
+    }
+
+    fn base_terrain(&self) -> &terrain::Terrain {
+        &self.terrains[0]
+    }
+
+    /// Adds a tile continuing the selected terrain's world one unit east of
+    /// its origin, placed one map width east of it, and selects the tile.
+    /// Neighbouring tiles only meet seamlessly on the flat map while it is
+    /// unrotated, as each wraps its own map rotation. The tile gets the next
+    /// object id above every terrain's, so `pick_at` tells them apart.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    fn add_terrain(&mut self) {
+        let source = self.terrain();
+        let mut transform = source.transform();
+        transform.offset.x += terrain::map_size(source.radius()).x * transform.scale;
+        let origin = source.origin() + glam::Vec2::X;
+        let highest = self.terrains.iter().map(terrain::Terrain::id).max();
+        let id = highest.unwrap_or(0) + 1;
+        let renderer = &self.terrain_renderer;
+        let mut tile = source.tile(&self.device, renderer, &self.world, origin, id);
+        tile.set_transform(transform);
+        tile.set_double_buffered(&self.device, source.double_buffered());
+        self.terrains.push(tile);
+        self.select_terrain(self.terrains.len() - 1);
+    }
+
+    /// Removes the selected terrain and selects the one before it. The base
+    /// terrain stays.
+    #[cfg(feature = "ui")]
+    fn remove_terrain(&mut self) {
+        if self.selected == 0 {
+            return;
         }
+        self.terrains.remove(self.selected);
+        self.select_terrain(self.selected - 1);
+    }
+
+    /// Also drops the paint history, whose snapshots belong to the terrain
+    /// that was selected before.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    fn select_terrain(&mut self, index: usize) {
+        self.selected = index.min(self.terrains.len() - 1);
+        self.paint_history = undo::UndoStack::new(UNDO_LIMIT);
+    }
+
+    /// Puts every overlay setting back to its startup value, regenerating a
+    /// terrain only if its shape or origin changes. Applies to every terrain;
+    /// only the base's origin is reset, as each tile's origin and transform
+    /// are what place it. The seeds, paint and camera are left alone.
+    #[cfg(feature = "ui")]
+    fn reset_settings(&mut self) {
+        let defaults = self.defaults;
+        self.map_auto_center = defaults.map_auto_center;
+        self.map_reframe = defaults.map_reframe;
+        self.map_transition = defaults.map_transition;
+        for terrain in &mut self.terrains {
+            terrain.set_settings(defaults.terrain);
+            terrain.set_detail(defaults.detail);
+            terrain.set_double_buffered(&self.device, defaults.double_buffer);
+        }
+        if self.base_terrain().origin() != defaults.origin {
+            self.terrains[0].set_origin(defaults.origin);
+        }
+        self.sun = defaults.sun;
+        self.water.set_settings(&self.device, defaults.water);
+        self.weather.set_settings(defaults.weather);
+        self.brush = defaults.brush;
+        self.measure = defaults.measure;
+        self.stereo = defaults.stereo;
+        self.depth_mode = defaults.depth_mode;
+        self.horizon_cull = defaults.horizon_cull;
+        if defaults.pacing != self.pacing {
+            self.set_pacing(defaults.pacing);
+        }
+        self.ui_scale = defaults.ui_scale;
+    }
+
+    #[cfg(feature = "ui")]
+    fn export_csv(&self, path: &str) {
+        let result = std::fs::File::create(path).and_then(|file| self.terrain().write_csv(file));
+        match result {
+            Ok(()) => println!("wrote {path}"),
+            Err(err) => eprintln!("{path}: {err}"),
+        }
+    }
+
+    /// Renders the scene at `width` x `height` into an offscreen target and
+    /// saves it as a PNG. The projection follows the capture's aspect rather
+    /// than the window's; the overlay and stereo are left out.
+    fn capture_screenshot(&self, path: &str, width: u32, height: u32) {
+        let result = self
+            .render_capture(width, height, self.input.position, self.time)
+            .and_then(|capture| capture.save_png(&self.device, &self.queue, path));
+        match result {
+            Ok(()) => println!("wrote {path} ({width}x{height})"),
+            Err(err) => eprintln!("{path}: {err}"),
+        }
+    }
+
+    /// Offscreen render of the scene seen from `eye` at animation `time`.
+    fn render_capture(
+        &self,
+        width: u32,
+        height: u32,
+        eye: Vec3,
+        time: f32,
+    ) -> Result<screenshot::Capture, Box<dyn Error>> {
+        let max = self.device.limits().max_texture_dimension_2d;
+        if !(1..=max).contains(&width) || !(1..=max).contains(&height) {
+            return Err(format!("screenshot sides must be between 1 and {max} pixels").into());
+        }
+        let config = wgpu::SurfaceConfiguration {
+            width,
+            height,
+            ..self.scene_config.clone()
+        };
+        let capture = screenshot::Capture::new(&self.device, &config)?;
+        let aspect = width as f32 / height as f32;
+        self.upload_view(&view::SceneView {
+            time,
+            ..self.scene_view_with_aspect(eye, aspect)
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("screenshot encoder"),
+            });
+        self.draw_scene(&mut encoder, &capture.view, &capture.depth.view);
+        self.queue.submit(Some(encoder.finish()));
+        // Put the window's view back for the frame being drawn.
+        self.upload_view(&self.scene_view(self.input.position));
+        Ok(capture)
+    }
+
+    /// Starts rendering a turntable into `directory`, replacing any that is
+    /// still running. Frames follow in later ticks, and the window keeps
+    /// drawing meanwhile.
+    fn start_turntable(&mut self, settings: turntable::TurntableSettings, directory: &Path) {
+        let pose = self.input.pose();
+        match turntable::Turntable::new(settings, directory, pose, self.time) {
+            Ok(turntable) => self.turntable = Some(turntable),
+            Err(err) => eprintln!("{}: {err}", directory.display()),
+        }
+    }
+
+    /// Renders and saves the next turntable frame, ending the turntable after
+    /// its last frame or on the first error. Its own camera and time drive
+    /// the weather meanwhile, so particles step evenly from frame to frame.
+    fn render_turntable_frame(&mut self) {
+        let Some(turntable) = &mut self.turntable else {
+            return;
+        };
+        let Some(frame) = turntable.next_frame() else {
+            println!("wrote turntable to {}", turntable.directory().display());
+            self.end_turntable();
+            return;
+        };
+        let [width, height] = turntable.size();
+        let target = self.input.target;
+        let region = self.weather_region(frame.eye, target);
+        self.weather.update(&self.queue, region, frame.time);
+        let result = self
+            .render_capture(width, height, frame.eye, frame.time)
+            .and_then(|capture| {
+                let path = frame.path.to_string_lossy();
+                capture.save_png(&self.device, &self.queue, &path)
+            });
+        if let Err(err) = result {
+            eprintln!("{}: {err}\nturntable stopped", frame.path.display());
+            self.end_turntable();
+        }
+    }
+
+    /// Drops the turntable and hands the weather back to the window's clock.
+    /// The turntable's time runs ahead of it, so without the reset the
+    /// particles would hold still until the window caught up.
+    fn end_turntable(&mut self) {
+        self.turntable = None;
+        let region = self.weather_region(self.input.position, self.input.target);
+        self.weather.update(&self.queue, region, self.time);
+    }
+
+    /// Sleeps until the frame-rate cap allows the next frame to start.
+    fn pace_frame(&self) {
+        self.pacing.wait(self.last_frame);
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
         let mut handled = false;
         #[cfg(feature = "ui")]
-        let ui_captures_pointer = {
-            if self.gui.on_event(&self.window, event) {
-                handled = true;
+        let (ui_captures_pointer, ui_captures_keyboard) = match &mut self.presenter {
+            Some(Presenter { gui, window, .. }) => {
+                if gui.on_event(window, event) {
+                    handled = true;
+                }
+                match &self.viewport {
+                    // Embedded, the scene only gets input aimed at its image.
+                    Some(viewport) => (!viewport.layout.hovered, !viewport.layout.focused),
+                    None => (
+                        gui.ctx.wants_pointer_input(),
+                        gui.ctx.wants_keyboard_input(),
+                    ),
+                }
             }
-            self.gui.ctx.wants_pointer_input()
+            None => (false, false),
         };
         #[cfg(not(feature = "ui"))]
-        let ui_captures_pointer = false;
+        let (ui_captures_pointer, ui_captures_keyboard) = (false, false);
+
+        if let WindowEvent::CursorMoved { position, .. } = event {
+            let position = glam::Vec2::new(position.x as f32, position.y as f32);
+            self.cursor = self.scene_cursor(position);
+        }
 
         match event {
             WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == winit::event::ElementState::Pressed;
+                if *button == MouseButton::Left && !pressed {
+                    self.input.end_stroke();
+                }
                 if ui_captures_pointer {
                     return handled;
                 }
-                if *button == MouseButton::Left && *state == winit::event::ElementState::Pressed {
-                    self.input.active = true;
-                    self.set_cursor_grab(true);
+                if *button == MouseButton::Right && pressed {
+                    self.measurement.clear();
                     handled = true;
                 }
-            }
-            WindowEvent::MouseWheel { delta, .. } => {
-                if !ui_captures_pointer {
-                    handled |= self.input.handle_scroll(delta);
-                }
-            }
-            WindowEvent::CursorMoved { position, .. } => {
-                if !ui_captures_pointer {
-                    let pos = glam::Vec2::new(position.x as f32, position.y as f32);
-                    self.input.handle_cursor_move(pos);
+                if *button == MouseButton::Left && pressed {
+                    if self.measure.enabled {
+                        self.set_cursor_grab(false);
+                        self.place_measure_point();
+                    } else if self.brush.mode == brush::BrushMode::Off {
+                        self.input.active = true;
+                        self.set_cursor_grab(true);
+                    } else {
+                        // Brush strokes need a free cursor and a still camera.
+                        self.set_cursor_grab(false);
+                        self.input.begin_stroke();
+                    }
                     handled = true;
                 }
             }
+            WindowEvent::MouseWheel { delta, .. } if !ui_captures_pointer => {
+                handled |= self.input.handle_scroll(delta);
+            }
+            WindowEvent::CursorMoved { position, .. } if !ui_captures_pointer => {
+                let pos = glam::Vec2::new(position.x as f32, position.y as f32);
+                self.input.handle_cursor_move(pos);
+                handled = true;
+            }
             WindowEvent::Focused(false) => {
                 self.input.active = false;
                 self.set_cursor_grab(false);
@@ -182,7 +728,7 @@ impl State {
             WindowEvent::Focused(true) => {
                 self.input.active = false;
             }
-            WindowEvent::KeyboardInput { event, .. } => {
+            WindowEvent::KeyboardInput { event, .. } if !ui_captures_keyboard => {
                 handled |= self.input.handle_key(event);
                 if self.input.take_release_cursor() {
                     self.set_cursor_grab(false);
@@ -196,30 +742,66 @@ impl State {
     }
 
     fn set_cursor_grab(&self, grab: bool) {
+        let Some(window) = self.window() else {
+            return;
+        };
         if grab {
-            if self
-                .window
+            if window
                 .set_cursor_grab(CursorGrabMode::Locked)
-                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
                 .is_err()
             {
                 eprintln!("Could not lock cursor");
             }
-            let _ = self.window.set_cursor_visible(false);
+            window.set_cursor_visible(false);
         } else {
-            let _ = self.window.set_cursor_grab(CursorGrabMode::None);
-            let _ = self.window.set_cursor_visible(true);
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+            window.set_cursor_visible(true);
         }
     }
 
     fn toggle_map(&mut self) {
         if self.map_target < 0.5 {
-            let dir = self.input.position.normalize_or_zero();
-            let lon_center = dir.z.atan2(dir.x);
-            self.map_rotation = std::f32::consts::PI - lon_center;
+            // Auto-center recenters on the current view; otherwise the map
+            // keeps the rotation it had the last time it was flattened.
+            if self.map_auto_center {
+                let dir = self.input.position.normalize_or_zero();
+                let lon_center = dir.z.atan2(dir.x);
+                self.map_rotation = std::f32::consts::PI - lon_center;
+            }
             self.map_target = 1.0;
+            if self.map_reframe {
+                // Flattening again mid-way back keeps the original globe pose.
+                if !self.input.reframing() || self.globe_pose.is_none() {
+                    self.globe_pose = Some(self.input.pose());
+                }
+                self.input
+                    .reframe(self.map_framing(), transition::DEFAULT_SPEED);
+            } else {
+                self.globe_pose = None;
+            }
         } else {
             self.map_target = 0.0;
+            if let Some(pose) = self.globe_pose.filter(|_| self.map_reframe) {
+                self.input.reframe(pose, transition::DEFAULT_SPEED);
+            }
+        }
+    }
+
+    /// Camera pose that fits the whole flat map in the window.
+    fn map_framing(&self) -> input::CameraPose {
+        let aspect = self.scene_config.width.max(1) as f32 / self.scene_config.height.max(1) as f32;
+        let half_fov = (self.world.fov_y_degrees * 0.5).to_radians().tan();
+        let (min, max) = self.base_terrain().bounds(1.0);
+        let half_width = (max.x - min.x) * 0.5;
+        let half_height = (max.z - min.z) * 0.5;
+        let fit = (half_height / half_fov).max(half_width / (half_fov * aspect));
+        input::CameraPose {
+            // Looking towards +Z keeps north at the top of the screen.
+            yaw: 0.0,
+            pitch: self.world.map_framing_pitch,
+            orbit_radius: fit * 1.1,
+            target: Vec3::ZERO,
         }
     }
 
@@ -227,88 +809,600 @@ impl State {
         let now = Instant::now();
         let dt = (now - self.last_frame).as_secs_f32();
         self.last_frame = now;
+        self.tick(dt);
+    }
+
+    /// Advances the scene by `dt` seconds: camera integration, the map
+    /// transition, queued key actions and brush strokes, then uploads the
+    /// resulting view. Takes no time from the clock, so a host can step it at
+    /// whatever rate it likes.
+    pub fn tick(&mut self, dt: f32) {
+        if self.input.take_toggle_time_pause() {
+            self.time_paused = !self.time_paused;
+        }
+        if self.input.take_step_time() {
+            self.step_time();
+        }
+        if !self.time_paused {
+            self.time += dt;
+        } else if std::mem::take(&mut self.time_step) {
+            self.time += TIME_STEP;
+        }
 
         self.input.update(dt);
+        let (azimuth, elevation) = self.input.sun_nudge();
+        if azimuth != 0.0 || elevation != 0.0 {
+            self.sun.nudge(azimuth, elevation, dt);
+        }
+
+        #[cfg(feature = "hot-reload")]
+        if let Some(changed) = self.shader_watcher.as_ref().map(|w| w.changed()) {
+            self.reload_shaders(&changed);
+        }
         if self.input.take_toggle_map() {
             self.toggle_map();
         }
+        if self.input.take_toggle_stereo() {
+            self.stereo.enabled = !self.stereo.enabled;
+        }
+        if self.input.take_cycle_depth_mode() {
+            self.depth_mode = self.depth_mode.next();
+        }
+        if self.input.take_cycle_brush() {
+            self.brush.mode = self.brush.mode.next();
+            self.input.end_stroke();
+        }
+        if self.input.take_toggle_measure() {
+            self.measure.enabled = !self.measure.enabled;
+            self.input.end_stroke();
+        }
+        if self.input.take_identify() {
+            self.identify();
+        }
+        #[cfg(feature = "ui")]
+        {
+            let step = self.input.take_ui_scale_step();
+            if self.input.take_ui_scale_reset() {
+                self.ui_scale = DEFAULT_UI_SCALE;
+            }
+            self.ui_scale =
+                (self.ui_scale + step as f32 * UI_SCALE_STEP).clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+        }
+        if self.input.take_undo() {
+            self.undo_paint();
+        }
+        if self.input.take_redo() {
+            self.redo_paint();
+        }
+
+        self.map_blend = self
+            .map_transition
+            .step(self.map_blend, self.map_target, dt);
+        // A running turntable steps the weather itself, at its own pace.
+        if self.turntable.is_none() {
+            let region = self.weather_region(self.input.position, self.input.target);
+            self.weather.update(&self.queue, region, self.time);
+        }
+        let scene_view = self.scene_view(self.input.position);
+        self.view_proj = scene_view.view_proj;
+        self.upload_view(&scene_view);
+        self.render_turntable_frame();
+        let stroke_began = self.input.take_stroke_begin();
+        if self.brush.mode == brush::BrushMode::Regenerate {
+            // Once per click: dragging would keep rerolling the same patch.
+            if stroke_began {
+                self.regenerate_region();
+            }
+        } else {
+            if stroke_began {
+                let terrain = &self.terrains[self.selected];
+                self.paint_history.begin(|| terrain.paint_layer());
+            }
+            if stroke_began || self.input.stroke_active() {
+                self.apply_brush();
+            }
+        }
+        if self.input.take_stroke_end() {
+            self.paint_history.commit();
+        }
+
+        if self.input.take_randomize() {
+            self.randomize_terrain();
+        }
+        if self.input.take_smooth() {
+            self.terrains[self.selected].smooth(&self.queue, terrain::DEFAULT_SMOOTH_PASSES);
+        }
+        if self.input.take_screenshot() {
+            let scale = screenshot::DEFAULT_SCALE;
+            self.capture_screenshot(
+                screenshot::DEFAULT_PATH,
+                self.scene_config.width * scale,
+                self.scene_config.height * scale,
+            );
+        }
+        for terrain in &mut self.terrains {
+            terrain.poll_generation(&self.queue);
+        }
+        // The base terrain owns the waterline; the water layer follows it so
+        // the two never disagree about where the coast is.
+        let base = &self.terrains[0];
+        if base.revision() != self.water_revision {
+            self.water.set_level(&self.device, base.water_level());
+            self.water.set_heightfield(&self.queue, base.heights());
+            self.water_revision = base.revision();
+        }
+    }
+
+    /// Pauses the animation clock if it is running and queues one
+    /// `TIME_STEP` of it.
+    fn step_time(&mut self) {
+        self.time_paused = true;
+        self.time_step = true;
+    }
+
+    fn randomize_terrain(&mut self) {
+        let jitter_water = self.water.settings().jitter_level;
+        self.terrains[self.selected].randomize(&mut self.rng, jitter_water);
+    }
+
+    fn undo_paint(&mut self) {
+        let terrain = &mut self.terrains[self.selected];
+        if let Some(paint) = self.paint_history.undo(terrain.paint_layer()) {
+            terrain.set_paint_layer(&self.queue, paint);
+        }
+    }
+
+    fn redo_paint(&mut self) {
+        let terrain = &mut self.terrains[self.selected];
+        if let Some(paint) = self.paint_history.redo(terrain.paint_layer()) {
+            terrain.set_paint_layer(&self.queue, paint);
+        }
+    }
+
+    /// The selected terrain's paint and the overlay scale, as `save_session`
+    /// writes them.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    fn session(&self) -> session::Session {
+        let terrain = self.terrain();
+        let paint = session::PaintLayer {
+            grid: terrain.grid(),
+            texels: terrain.paint_layer(),
+        };
+        #[cfg(feature = "ui")]
+        let ui_scale = Some(self.ui_scale);
+        #[cfg(not(feature = "ui"))]
+        let ui_scale = None;
+        session::Session {
+            paint: Some(paint),
+            ui_scale,
+        }
+    }
+
+    /// Paints the selected terrain with a loaded session's layer, as one undo
+    /// step, and makes its overlay scale the current and reset value. The
+    /// layer has to match the terrain's grid; nothing is applied if it does
+    /// not.
+    fn apply_session(&mut self, session: session::Session) -> Result<(), String> {
+        let terrain = &mut self.terrains[self.selected];
+        if let Some(paint) = &session.paint {
+            if paint.grid != terrain.grid() {
+                let [ours, theirs] = [terrain.grid().lat, paint.grid.lat];
+                return Err(format!("paint is for a {theirs} row grid, not {ours}"));
+            }
+        }
+        if let Some(paint) = session.paint {
+            self.paint_history.record(terrain.paint_layer());
+            terrain.set_paint_layer(&self.queue, paint.texels);
+        }
+        #[cfg(feature = "ui")]
+        if let Some(scale) = session.ui_scale {
+            self.ui_scale = scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+            self.defaults.ui_scale = self.ui_scale;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "ui")]
+    fn save_session(&self, path: &str) {
+        match self.session().save(Path::new(path)) {
+            Ok(()) => println!("wrote {path}"),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn load_session(&mut self, path: &str) {
+        match session::Session::load(Path::new(path)) {
+            Ok(session) => {
+                if let Err(err) = self.apply_session(session) {
+                    eprintln!("{path}: {err}");
+                }
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+
+    /// Picks the object under the cursor for the overlay to show.
+    fn identify(&mut self) {
+        let x = self.cursor.x.max(0.0) as u32;
+        let y = self.cursor.y.max(0.0) as u32;
+        self.identified = Some(([x, y], self.pick_at(x, y)));
+    }
+
+    /// Returns the id of the object covering pixel (`x`, `y`), or `None` over
+    /// background. Renders a one-texel id pass and blocks on its readback, so
+    /// call it on demand rather than every frame.
+    fn pick_at(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.scene_config.width || y >= self.scene_config.height {
+            return None;
+        }
+        self.upload_view(&self.scene_view(self.input.position));
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("id encoder"),
+            });
+        {
+            let mut pass =
+                self.ids
+                    .begin_pass(&mut encoder, &self.depth.view, self.depth_mode, x, y);
+            self.terrain_renderer
+                .draw_id(&mut pass, &self.terrains, self.depth_mode);
+        }
+        self.ids.copy_texel(&mut encoder, x, y);
+        self.queue.submit(Some(encoder.finish()));
+        self.ids.read_texel(&self.device)
+    }
+
+    /// Map coordinates of `terrain` under the cursor.
+    fn pick_cursor(&self, terrain: &terrain::Terrain) -> Option<glam::Vec2> {
+        let ray = picking::cursor_ray(
+            self.view_proj,
+            self.input.position,
+            self.cursor,
+            self.scene_config.width,
+            self.scene_config.height,
+        );
+        terrain.pick(&ray, self.map_blend, self.map_rotation)
+    }
+
+    fn apply_brush(&mut self) {
+        if let Some(uv) = self.pick_cursor(self.terrain()) {
+            self.terrains[self.selected].paint(&self.queue, uv, &self.brush);
+        }
+    }
+
+    /// Rerolls the brush area under the cursor. Heights are not part of the
+    /// paint undo history, so this cannot be undone.
+    fn regenerate_region(&mut self) {
+        let Some(uv) = self.pick_cursor(self.terrain()) else {
+            return;
+        };
+        let (queue, rng) = (&self.queue, &mut self.rng);
+        if !self.terrains[self.selected].regenerate_region(queue, rng, uv, &self.brush) {
+            eprintln!("terrain is still generating; region not regenerated");
+        }
+    }
+
+    fn place_measure_point(&mut self) {
+        let Some(uv) = self.pick_cursor(self.base_terrain()) else {
+            return;
+        };
+        self.measurement.add(uv);
+    }
 
-        let blend_step = 1.0 - (-MAP_TRANSITION_SPEED * dt).exp();
-        self.map_blend += (self.map_target - self.map_blend) * blend_step;
-        self.map_blend = self.map_blend.clamp(0.0, 1.0);
-        let aspect = self.config.width.max(1) as f32 / self.config.height.max(1) as f32;
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    fn measure_path(&self) -> Option<Vec<Vec3>> {
+        let (a, b) = self.measurement.endpoints()?;
+        Some(self.base_terrain().surface_path(
+            a,
+            b,
+            measure::PATH_SAMPLES,
+            self.map_blend,
+            self.map_rotation,
+        ))
+    }
+
+    /// The current measurement projected for the overlay.
+    #[cfg(feature = "ui")]
+    fn measure_overlay(&self) -> Option<measure::MeasureOverlay> {
+        let (path, distances) = match self.measure_path() {
+            Some(path) => {
+                let distances = measure::Distances::of_path(&path, self.base_terrain().radius());
+                (path, Some(distances))
+            }
+            None => {
+                let start = self.measurement.start()?;
+                let point =
+                    self.base_terrain()
+                        .surface_point(start, self.map_blend, self.map_rotation);
+                (vec![point], None)
+            }
+        };
         let eye = self.input.position;
-        let orbit = eye.length().max(1.0);
+        let (width, height) = (self.scene_config.width, self.scene_config.height);
+        let origin = self
+            .viewport
+            .as_ref()
+            .map_or(glam::Vec2::ZERO, |viewport| viewport.layout.origin);
+        let screen_path = path
+            .iter()
+            .map(|&point| {
+                // On the globe, drop samples on the far side of the planet.
+                if self.map_blend < 0.5 && (eye - point).dot(point) < 0.0 {
+                    return None;
+                }
+                measure::project(self.view_proj, point, width, height).map(|p| p + origin)
+            })
+            .collect();
+        Some(measure::MeasureOverlay {
+            screen_path,
+            distances,
+        })
+    }
+
+    /// Where rain or snow falls: on the terrain along the line of sight from
+    /// `eye` to `target`, or nowhere while weather is off or that looks past
+    /// the globe. Cold ground, the temperatures that grow tundra or ice, gets
+    /// snow.
+    fn weather_region(&self, eye: Vec3, target: Vec3) -> Option<weather::Region> {
+        if !self.weather.settings().enabled {
+            return None;
+        }
+        let ray = picking::Ray {
+            origin: eye,
+            dir: (target - eye).normalize_or_zero(),
+        };
+        let base = self.base_terrain();
+        let uv = base.pick(&ray, self.map_blend, self.map_rotation)?;
+        let surface = base.surface_point(uv, self.map_blend, self.map_rotation);
+        let t = self.map_blend.clamp(0.0, 1.0);
+        let up = surface.normalize_or_zero().lerp(Vec3::Y, t);
+        let cold = base.settings().tundra_temperature_max;
+        Some(weather::Region {
+            surface,
+            up,
+            snow: base.temperature_at(uv) < cold,
+        })
+    }
+
+    fn scene_view(&self, eye: Vec3) -> view::SceneView {
+        let aspect = self.scene_config.width.max(1) as f32 / self.scene_config.height.max(1) as f32;
+        self.scene_view_with_aspect(eye, aspect)
+    }
+
+    fn scene_view_with_aspect(&self, eye: Vec3, aspect: f32) -> view::SceneView {
+        let orbit = self.input.position.length().max(1.0);
         let up = Vec3::Y;
-        let view = Mat4::look_at_rh(eye, Vec3::ZERO, up);
+        let view = Mat4::look_at_rh(eye, self.input.target, up);
         let near = 1.0;
-        let far = (orbit + terrain::WORLD_RADIUS * 4.0).max(terrain::WORLD_RADIUS * 6.0);
-        let proj = Mat4::perspective_rh(50f32.to_radians(), aspect, near, far);
-        let view_proj = proj * view;
-        self.terrain
-            .update_view(&self.queue, view_proj, self.map_blend, self.map_rotation);
-        self.water
-            .update_view(&self.queue, view_proj, self.map_blend, self.map_rotation);
+        let radius = self.base_terrain().radius();
+        // Tiles and inserts may sit well beyond the base globe.
+        let tiles = &self.terrains[1..];
+        let reach = tiles.iter().map(|t| t.reach()).fold(0.0, f32::max);
+        let far = (orbit + radius * 4.0).max(radius * 6.0).max(orbit + reach);
+        let fov = self.world.fov_y_degrees.to_radians();
+        let proj = if self.depth_mode.is_reversed() {
+            Mat4::perspective_rh(fov, aspect, far, near)
+        } else {
+            Mat4::perspective_rh(fov, aspect, near, far)
+        };
+        view::SceneView {
+            view_proj: proj * view,
+            eye,
+            morph: self.map_blend,
+            rotation: self.map_rotation,
+            sun_dir: self.sun.direction(),
+            lighting: self.sun.lighting_uniform(),
+            ambient: self.sun.ambient_uniform(),
+            depth_mode: self.depth_mode,
+            horizon_cull: self.horizon_cull,
+            far,
+            time: self.time,
+        }
+    }
 
-        if self.input.take_randomize() {
-            self.terrain.randomize(&self.queue, &mut self.rng);
+    fn upload_view(&self, view: &view::SceneView) {
+        for terrain in &self.terrains {
+            terrain.update_view(&self.queue, view);
         }
+        self.water.update_view(&self.queue, view);
+        self.weather.update_view(&self.queue, view);
     }
 
-    fn render(&mut self) -> Result<(), SurfaceError> {
-        let frame = self.surface.get_current_texture()?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder =
-            self.device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("render encoder"),
-                });
+    /// Records the scene passes in a fixed order:
+    ///
+    /// 0. weather simulation: a compute pass stepping the rain and snow
+    ///    particles, recorded once per `tick` and only while they are shown;
+    /// 1. terrain pass: clears color and depth, draws the opaque terrain and
+    ///    writes depth;
+    /// 2. water pass: loads both attachments and draws the translucent water,
+    ///    depth-tested against the terrain but never writing depth. Skipped
+    ///    entirely when the water layer is disabled;
+    /// 3. weather pass: the same, drawing the particles as blended billboards
+    ///    over terrain and water. Skipped while there are none to show.
+    ///
+    /// The egui overlay is drawn afterwards, on top, in `render`.
+    fn draw_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) {
+        self.weather.simulate(encoder);
+        self.draw_terrain_pass(encoder, view, depth);
+        if self.water.settings().enabled {
+            let mut pass = begin_overlay_pass(encoder, "water pass", view, depth);
+            self.water.draw(&mut pass, self.depth_mode);
+        }
+        if self.weather.is_visible() {
+            let mut pass = begin_overlay_pass(encoder, "weather pass", view, depth);
+            self.weather.draw(&mut pass, self.depth_mode);
+        }
+    }
 
+    fn draw_terrain_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) {
         let clear = wgpu::Color {
             r: self.clear.x as f64,
             g: self.clear.y as f64,
             b: self.clear.z as f64,
-            a: 1.0,
+            a: self.clear.w as f64,
         };
 
-        {
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("terrain pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(clear),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("terrain pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.depth_mode.clear_value()),
+                    store: wgpu::StoreOp::Store,
                 }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.terrain_renderer
+            .draw(&mut pass, &self.terrains, self.depth_mode);
+    }
+
+    /// Draws the scene into `view` and submits it, without touching the
+    /// window surface or the overlay. `view` must match the scene target's
+    /// size and format (`scene_config`), since the depth and stereo targets
+    /// are shared. Uses the view uploaded by the last `tick`.
+    pub fn render_to(&self, view: &wgpu::TextureView) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("scene encoder"),
             });
-            self.terrain.draw(&mut pass);
-            self.water.draw(&mut pass);
+        if self.stereo.enabled {
+            // Each eye gets its own submit so the uniform write for the
+            // second eye doesn't overwrite the first before it is drawn.
+            let eyes = self
+                .stereo
+                .eyes(self.input.position, self.input.target, Vec3::Y);
+            for (i, eye) in eyes.into_iter().enumerate() {
+                self.upload_view(&self.scene_view(eye));
+                let mut eye_encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("eye encoder"),
+                        });
+                self.draw_scene(
+                    &mut eye_encoder,
+                    self.anaglyph.eye_view(i),
+                    &self.depth.view,
+                );
+                self.queue.submit(Some(eye_encoder.finish()));
+            }
+            self.anaglyph.composite(&mut encoder, view);
+        } else {
+            self.draw_scene(&mut encoder, view, &self.depth.view);
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Draws a frame to the window, with the overlay on top. A headless state
+    /// has no window and draws nothing; its host calls `render_to` instead.
+    fn render(&mut self) -> Result<(), SurfaceError> {
+        let Some(presenter) = &self.presenter else {
+            return Ok(());
+        };
+        let frame = presenter.surface.get_current_texture()?;
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Embedded, the scene goes to the viewport image and the window itself
+        // only shows egui, which draws that image.
+        #[cfg(feature = "ui")]
+        self.render_to(self.viewport.as_ref().map_or(&view, |v| v.view()));
+        #[cfg(not(feature = "ui"))]
+        self.render_to(&view);
+
+        #[cfg(feature = "ui")]
+        let mut encoder =
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("render encoder"),
+                });
+        #[cfg(feature = "ui")]
+        if self.viewport.is_some() {
+            clear_target(&mut encoder, &view, wgpu::Color::BLACK);
         }
 
         #[cfg(feature = "ui")]
-        let ui_frame = self.gui.draw(
-            &self.window,
+        let measure_overlay = self.measure_overlay();
+        #[cfg(feature = "ui")]
+        let terrain = &self.terrains[self.selected];
+        #[cfg(feature = "ui")]
+        let Some(Presenter {
+            gui,
+            window,
+            config,
+            info,
+            ..
+        }) = &mut self.presenter
+        else {
+            unreachable!("checked above");
+        };
+        #[cfg(feature = "ui")]
+        let ui_frame = gui.draw(
+            window,
             &self.device,
             &self.queue,
             &view,
             &mut encoder,
-            &self.config,
-            self.map_target,
-            self.terrain.settings(),
+            config,
+            UiParams {
+                map_target: self.map_target,
+                map_auto_center: self.map_auto_center,
+                map_reframe: self.map_reframe,
+                map_transition: self.map_transition,
+                terrain_count: self.terrains.len(),
+                selected_terrain: self.selected,
+                transform: terrain.transform(),
+                settings: terrain.settings(),
+                origin: terrain.origin(),
+                stereo: self.stereo,
+                sun: self.sun,
+                detail: terrain.detail(),
+                water: self.water.settings(),
+                weather: self.weather.settings(),
+                brush: self.brush,
+                depth_mode: self.depth_mode,
+                horizon_cull: self.horizon_cull,
+                double_buffer: terrain.double_buffered(),
+                time: self.time,
+                time_paused: self.time_paused,
+                draw_stats: [terrain.draw_stats(), self.water.draw_stats()],
+                uniform_uploads: [terrain.uniform_uploads(), self.water.uniform_uploads()],
+                pacing: self.pacing,
+                ui_scale: self.ui_scale,
+                undo_len: self.paint_history.undo_len(),
+                redo_len: self.paint_history.redo_len(),
+                generating: terrain.generation_progress(),
+                turntable: self.turntable.as_ref().map(|t| t.fraction()),
+                measure: self.measure,
+                measure_overlay,
+                identified: self.identified,
+                viewport: self.viewport.as_ref().map(|v| v.texture_id()),
+                info,
+            },
         );
 
         #[cfg(feature = "ui")]
@@ -316,19 +1410,94 @@ impl State {
             let mut submits = ui_frame.commands;
             submits.push(encoder.finish());
             self.queue.submit(submits);
+            if let Some(layout) = ui_frame.viewport {
+                self.set_viewport_layout(layout);
+            }
+            let terrain = &mut self.terrains[self.selected];
             if ui_frame.settings_changed {
-                self.terrain
-                    .set_settings(&self.queue, ui_frame.settings);
+                terrain.set_settings(ui_frame.settings);
+            }
+            if ui_frame.origin != terrain.origin() {
+                terrain.set_origin(ui_frame.origin);
             }
             if ui_frame.randomize {
-                self.terrain.randomize(&self.queue, &mut self.rng);
+                self.randomize_terrain();
             }
+            if let Some(passes) = ui_frame.smooth {
+                self.terrains[self.selected].smooth(&self.queue, passes);
+            }
+            if let Some(path) = &ui_frame.export_csv {
+                self.export_csv(path);
+            }
+            if let Some(path) = &ui_frame.save_session {
+                self.save_session(path);
+            }
+            if let Some(path) = &ui_frame.load_session {
+                self.load_session(path);
+            }
+            if let Some((path, [width, height])) = &ui_frame.screenshot {
+                self.capture_screenshot(path, *width, *height);
+            }
+            if let Some((directory, settings)) = &ui_frame.turntable {
+                self.start_turntable(*settings, directory.as_ref());
+            }
+            if ui_frame.cancel_turntable {
+                self.end_turntable();
+            }
+            self.map_auto_center = ui_frame.map_auto_center;
+            self.map_reframe = ui_frame.map_reframe;
+            self.map_transition = ui_frame.map_transition;
             if ui_frame.toggle_map {
                 self.toggle_map();
             }
+            self.stereo = ui_frame.stereo;
+            self.sun = ui_frame.sun;
+            self.terrains[self.selected].set_detail(ui_frame.detail);
+            self.water.set_settings(&self.device, ui_frame.water);
+            self.weather.set_settings(ui_frame.weather);
+            self.brush = ui_frame.brush;
+            self.depth_mode = ui_frame.depth_mode;
+            self.horizon_cull = ui_frame.horizon_cull;
+            self.terrains[self.selected].set_double_buffered(&self.device, ui_frame.double_buffer);
+            self.time_paused = ui_frame.time_paused;
+            if ui_frame.step_time {
+                self.step_time();
+            }
+            self.ui_scale = ui_frame.ui_scale;
+            if ui_frame.pacing != self.pacing {
+                self.set_pacing(ui_frame.pacing);
+            }
+            if ui_frame.clear_paint {
+                let terrain = &mut self.terrains[self.selected];
+                self.paint_history.record(terrain.paint_layer());
+                terrain.clear_paint(&self.queue);
+            }
+            if ui_frame.undo {
+                self.undo_paint();
+            }
+            if ui_frame.redo {
+                self.redo_paint();
+            }
+            self.measure = ui_frame.measure;
+            if ui_frame.clear_measure {
+                self.measurement.clear();
+            }
+            if ui_frame.reset {
+                self.reset_settings();
+            }
+            // Last, since everything above edits the terrain the overlay was
+            // showing.
+            if self.selected != 0 {
+                self.terrains[self.selected].set_transform(ui_frame.transform);
+            }
+            if ui_frame.add_terrain {
+                self.add_terrain();
+            } else if ui_frame.remove_terrain {
+                self.remove_terrain();
+            } else if ui_frame.selected_terrain != self.selected {
+                self.select_terrain(ui_frame.selected_terrain);
+            }
         }
-        #[cfg(not(feature = "ui"))]
-        self.queue.submit(Some(encoder.finish()));
 
         frame.present();
         Ok(())
@@ -340,21 +1509,113 @@ struct Gui {
     ctx: egui::Context,
     state: egui_winit::State,
     renderer: egui_wgpu::Renderer,
+    smooth_passes: u32,
+    export_path: String,
+    session_path: String,
+    screenshot_path: String,
+    screenshot_size: [u32; 2],
+    turntable_path: String,
+    turntable: turntable::TurntableSettings,
+}
+
+#[cfg(feature = "ui")]
+struct UiParams<'a> {
+    map_target: f32,
+    map_auto_center: bool,
+    map_reframe: bool,
+    map_transition: transition::MapTransition,
+    terrain_count: usize,
+    /// The terrain every terrain setting below belongs to.
+    selected_terrain: usize,
+    transform: terrain::TerrainTransform,
+    settings: terrain::TerrainSettings,
+    origin: glam::Vec2,
+    stereo: stereo::StereoSettings,
+    sun: sun::SunSettings,
+    detail: detail::DetailSettings,
+    water: water::WaterSettings,
+    weather: weather::WeatherSettings,
+    brush: brush::BrushSettings,
+    depth_mode: depth::DepthMode,
+    horizon_cull: bool,
+    double_buffer: bool,
+    time: f32,
+    time_paused: bool,
+    /// Selected terrain then water, after culling.
+    draw_stats: [mesh::DrawStats; 2],
+    /// Terrain and water view uniform writes so far.
+    uniform_uploads: [u32; 2],
+    pacing: pacing::FramePacing,
+    ui_scale: f32,
+    undo_len: usize,
+    redo_len: usize,
+    /// Progress of a terrain regenerate running in the background.
+    generating: Option<f32>,
+    /// Progress of a turntable being rendered.
+    turntable: Option<f32>,
+    measure: measure::MeasureSettings,
+    measure_overlay: Option<measure::MeasureOverlay>,
+    identified: Option<([u32; 2], Option<u32>)>,
+    /// Scene image to lay out in the central panel, when embedded.
+    viewport: Option<egui::TextureId>,
+    info: &'a info::SystemInfo,
 }
 
 #[cfg(feature = "ui")]
 struct UiFrame {
     commands: Vec<wgpu::CommandBuffer>,
     randomize: bool,
+    /// Applied after every other field, so it wins over this frame's edits.
+    reset: bool,
+    smooth: Option<u32>,
+    export_csv: Option<String>,
+    /// Path of a session to write with the selected terrain's paint.
+    save_session: Option<String>,
+    /// Path of a session to paint the selected terrain from.
+    load_session: Option<String>,
+    /// Output path and size of a requested screenshot.
+    screenshot: Option<(String, [u32; 2])>,
+    /// Output directory and settings of a requested turntable.
+    turntable: Option<(String, turntable::TurntableSettings)>,
+    cancel_turntable: bool,
     toggle_map: bool,
+    map_auto_center: bool,
+    map_reframe: bool,
+    map_transition: transition::MapTransition,
+    selected_terrain: usize,
+    add_terrain: bool,
+    remove_terrain: bool,
+    transform: terrain::TerrainTransform,
     settings: terrain::TerrainSettings,
     settings_changed: bool,
+    origin: glam::Vec2,
+    stereo: stereo::StereoSettings,
+    sun: sun::SunSettings,
+    detail: detail::DetailSettings,
+    water: water::WaterSettings,
+    weather: weather::WeatherSettings,
+    brush: brush::BrushSettings,
+    clear_paint: bool,
+    undo: bool,
+    redo: bool,
+    measure: measure::MeasureSettings,
+    clear_measure: bool,
+    viewport: Option<viewport::ViewportLayout>,
+    depth_mode: depth::DepthMode,
+    horizon_cull: bool,
+    double_buffer: bool,
+    time_paused: bool,
+    step_time: bool,
+    pacing: pacing::FramePacing,
+    ui_scale: f32,
 }
 
 #[cfg(feature = "ui")]
 impl Gui {
     fn new(window: &Window, device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
         let ctx = egui::Context::default();
+        // Ctrl +/- drive State::ui_scale instead, so the slider stays in sync.
+        ctx.options_mut(|options| options.zoom_with_keyboard = false);
         let state = egui_winit::State::new(
             ctx.clone(),
             egui::ViewportId::ROOT,
@@ -367,6 +1628,16 @@ impl Gui {
             ctx,
             state,
             renderer,
+            smooth_passes: terrain::DEFAULT_SMOOTH_PASSES,
+            export_path: DEFAULT_EXPORT_PATH.to_owned(),
+            session_path: session::DEFAULT_PATH.to_owned(),
+            screenshot_path: screenshot::DEFAULT_PATH.to_owned(),
+            screenshot_size: {
+                let size = window.inner_size();
+                [size.width, size.height].map(|side| side.max(1) * screenshot::DEFAULT_SCALE)
+            },
+            turntable_path: turntable::DEFAULT_DIRECTORY.to_owned(),
+            turntable: turntable::TurntableSettings::default(),
         }
     }
 
@@ -374,6 +1645,7 @@ impl Gui {
         self.state.on_window_event(window, event).consumed
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn draw(
         &mut self,
         window: &Window,
@@ -382,14 +1654,57 @@ impl Gui {
         view: &wgpu::TextureView,
         encoder: &mut wgpu::CommandEncoder,
         surface_config: &wgpu::SurfaceConfiguration,
-        map_target: f32,
-        settings: terrain::TerrainSettings,
+        params: UiParams<'_>,
     ) -> UiFrame {
         let raw_input = self.state.take_egui_input(window);
+        let map_target = params.map_target;
         let mut randomize = false;
+        let mut reset = false;
+        let mut smooth = None;
+        let mut smooth_passes = self.smooth_passes;
+        let mut export_path = std::mem::take(&mut self.export_path);
+        let mut export_csv = None;
+        let mut session_path = std::mem::take(&mut self.session_path);
+        let mut save_session = None;
+        let mut load_session = None;
+        let mut screenshot_path = std::mem::take(&mut self.screenshot_path);
+        let mut screenshot_size = self.screenshot_size;
+        let mut screenshot = None;
+        let mut turntable_path = std::mem::take(&mut self.turntable_path);
+        let mut turntable_settings = self.turntable;
+        let mut turntable = None;
+        let mut cancel_turntable = false;
         let mut toggle_map = false;
-        let mut settings = settings;
+        let mut map_auto_center = params.map_auto_center;
+        let mut map_reframe = params.map_reframe;
+        let mut map_transition = params.map_transition;
+        let mut selected_terrain = params.selected_terrain;
+        let mut add_terrain = false;
+        let mut remove_terrain = false;
+        let mut transform = params.transform;
+        let mut settings = params.settings;
         let mut settings_changed = false;
+        let mut origin = params.origin;
+        let mut stereo = params.stereo;
+        let mut sun = params.sun;
+        let mut detail = params.detail;
+        let mut water = params.water;
+        let mut weather = params.weather;
+        let mut brush = params.brush;
+        let mut clear_paint = false;
+        let mut undo = false;
+        let mut redo = false;
+        let mut measure = params.measure;
+        let mut clear_measure = false;
+        let mut viewport_layout = None;
+        let mut depth_mode = params.depth_mode;
+        let mut horizon_cull = params.horizon_cull;
+        let mut double_buffer = params.double_buffer;
+        let mut time_paused = params.time_paused;
+        let mut step_time = false;
+        let mut pacing = params.pacing;
+        let mut ui_scale = params.ui_scale;
+        self.ctx.set_zoom_factor(ui_scale);
         let full_output = self.ctx.run(raw_input, |ctx| {
             egui::Window::new("Overlay")
                 .resizable(false)
@@ -403,9 +1718,89 @@ impl Gui {
                     if ui.button(label).clicked() {
                         toggle_map = true;
                     }
-                    if ui.button("Randomise").clicked() {
-                        randomize = true;
+                    ui.checkbox(&mut map_auto_center, "Auto-center map on view");
+                    ui.checkbox(&mut map_reframe, "Reframe camera on flatten");
+                    ui.horizontal(|ui| {
+                        let (mut fixed, mut seconds) = match map_transition {
+                            transition::MapTransition::Fixed { seconds } => (true, seconds),
+                            _ => (false, transition::DEFAULT_SECONDS),
+                        };
+                        ui.checkbox(&mut fixed, "Fixed-duration flatten");
+                        ui.add_enabled(
+                            fixed,
+                            egui::Slider::new(&mut seconds, 0.2..=5.0).suffix(" s"),
+                        );
+                        if fixed {
+                            map_transition = transition::MapTransition::Fixed { seconds };
+                        } else if !matches!(
+                            map_transition,
+                            transition::MapTransition::Exponential { .. }
+                        ) {
+                            map_transition = transition::MapTransition::default();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Randomise").clicked() {
+                            randomize = true;
+                        }
+                        if ui.button("Reset to defaults").clicked() {
+                            reset = true;
+                        }
+                    });
+                    if let Some(progress) = params.generating {
+                        ui.add(
+                            egui::ProgressBar::new(progress)
+                                .show_percentage()
+                                .text("Generating terrain"),
+                        );
                     }
+                    ui.horizontal(|ui| {
+                        if ui.button("Smooth").clicked() {
+                            smooth = Some(smooth_passes);
+                        }
+                        ui.add(
+                            egui::Slider::new(&mut smooth_passes, 1..=terrain::MAX_SMOOTH_PASSES)
+                                .text("Passes"),
+                        );
+                    });
+                    ui.separator();
+                    ui.label("Terrains");
+                    let terrain_label = |index: usize| match index {
+                        0 => "Base".to_owned(),
+                        _ => format!("Tile {index}"),
+                    };
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_label("Editing")
+                            .selected_text(terrain_label(selected_terrain))
+                            .show_ui(ui, |ui| {
+                                for index in 0..params.terrain_count {
+                                    let label = terrain_label(index);
+                                    ui.selectable_value(&mut selected_terrain, index, label);
+                                }
+                            });
+                        if ui.button("Add tile").clicked() {
+                            add_terrain = true;
+                        }
+                        let base = params.selected_terrain == 0;
+                        if ui.add_enabled(!base, egui::Button::new("Remove")).clicked() {
+                            remove_terrain = true;
+                        }
+                    });
+                    // The water and map framing assume the base stays put.
+                    ui.add_enabled_ui(params.selected_terrain != 0, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Offset");
+                            let offset = &mut transform.offset;
+                            ui.add(egui::DragValue::new(&mut offset.x).speed(1.0).prefix("x "));
+                            ui.add(egui::DragValue::new(&mut offset.y).speed(1.0).prefix("y "));
+                            ui.add(egui::DragValue::new(&mut offset.z).speed(1.0).prefix("z "));
+                        });
+                        ui.add(
+                            egui::Slider::new(&mut transform.scale, 0.05..=4.0)
+                                .logarithmic(true)
+                                .text("Scale"),
+                        );
+                    });
                     ui.separator();
                     ui.label("Land & biomes");
                     settings_changed |= ui
@@ -420,6 +1815,27 @@ impl Gui {
                                 .text("Land elevation bias"),
                         )
                         .changed();
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.feature_size, 0.25..=4.0)
+                                .logarithmic(true)
+                                .text("Feature size"),
+                        )
+                        .changed();
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.warp_strength, 0.0..=0.6)
+                                .text("Warp strength"),
+                        )
+                        .changed();
+                    settings_changed |= ui
+                        .add_enabled(
+                            settings.warp_strength > 0.0,
+                            egui::Slider::new(&mut settings.warp_scale, 0.5..=8.0)
+                                .logarithmic(true)
+                                .text("Warp scale"),
+                        )
+                        .changed();
                     settings_changed |= ui
                         .add(
                             egui::Slider::new(&mut settings.temperature_bias, -0.2..=0.25)
@@ -448,8 +1864,423 @@ impl Gui {
                         settings.semi_arid_moisture_max = settings.desert_moisture_max;
                         settings_changed = true;
                     }
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.snow_temperature_max, 0.0..=0.5)
+                                .text("Snow temperature max"),
+                        )
+                        .changed();
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.tundra_temperature_max, 0.0..=0.6)
+                                .text("Tundra temperature max"),
+                        )
+                        .changed();
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.taiga_temperature_max, 0.0..=0.8)
+                                .text("Taiga temperature max"),
+                        )
+                        .changed();
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.tropical_temperature_min, 0.3..=1.0)
+                                .text("Tropical temperature min"),
+                        )
+                        .changed();
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(
+                                &mut settings.biome_blend,
+                                0.0..=terrain::MAX_BIOME_BLEND,
+                            )
+                            .text("Biome blend"),
+                        )
+                        .changed();
+                    ui.horizontal(|ui| {
+                        ui.label("Base color");
+                        settings_changed |=
+                            ui.color_edit_button_rgb(&mut settings.base_color).changed();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Seabed color");
+                        settings_changed |= ui
+                            .color_edit_button_rgb(&mut settings.seabed_color)
+                            .changed();
+                    });
+                    settings_changed |= ui
+                        .add(
+                            egui::Slider::new(&mut settings.seabed_transition, 0.0..=5.0)
+                                .text("Seabed transition"),
+                        )
+                        .changed();
+                    ui.label("Specular by biome");
+                    for (value, label) in [
+                        (&mut settings.snow_specular, "Snow"),
+                        (&mut settings.rock_specular, "Rock"),
+                        (&mut settings.sand_specular, "Sand"),
+                        (&mut settings.vegetation_specular, "Vegetation"),
+                    ] {
+                        settings_changed |= ui
+                            .add(egui::Slider::new(value, 0.0..=1.0).text(label))
+                            .changed();
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Tile origin");
+                        ui.add(egui::DragValue::new(&mut origin.x).speed(1.0).prefix("x "));
+                        ui.add(egui::DragValue::new(&mut origin.y).speed(1.0).prefix("y "));
+                    });
+                    ui.separator();
+                    ui.label("Surface detail");
+                    ui.add(egui::Slider::new(&mut detail.strength, 0.0..=2.0).text("Strength"));
+                    ui.add(
+                        egui::Slider::new(&mut detail.tiling, 0.05..=2.0)
+                            .logarithmic(true)
+                            .text("Tiling (per unit)"),
+                    );
+                    ui.add(egui::Slider::new(&mut detail.mip_bias, 0.0..=4.0).text("Mip bias"));
+                    ui.add(
+                        egui::Slider::new(&mut detail.fade_distance, 0.0..=2000.0)
+                            .text("Distance fade"),
+                    )
+                    .on_hover_text("Widens biome edges and fades detail with distance; 0 is off");
+                    ui.separator();
+                    ui.label("Sun");
+                    ui.add(egui::Slider::new(&mut sun.azimuth, 0.0..=360.0).text("Azimuth ([ ])"));
+                    ui.add(
+                        egui::Slider::new(&mut sun.elevation, -90.0..=90.0).text("Elevation (; ')"),
+                    );
+                    egui::ComboBox::from_label("Lighting")
+                        .selected_text(sun.model.label())
+                        .show_ui(ui, |ui| {
+                            for model in sun::LightingModel::ALL {
+                                ui.selectable_value(&mut sun.model, model, model.label());
+                            }
+                        });
+                    ui.add_enabled(
+                        sun.model == sun::LightingModel::Toon,
+                        egui::Slider::new(
+                            &mut sun.toon_bands,
+                            sun::MIN_TOON_BANDS..=sun::MAX_TOON_BANDS,
+                        )
+                        .text("Toon bands"),
+                    );
+                    ui.horizontal(|ui| {
+                        ui.label("Sky ambient");
+                        ui.color_edit_button_rgb(&mut sun.sky_ambient);
+                        ui.label("Ground");
+                        ui.color_edit_button_rgb(&mut sun.ground_ambient);
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut sun.ambient_intensity, 0.0..=1.5)
+                            .text("Ambient intensity"),
+                    );
+                    ui.separator();
+                    ui.label("Water");
+                    ui.checkbox(&mut water.enabled, "Show water");
+                    ui.checkbox(&mut water.jitter_level, "Jitter level on randomize");
+                    egui::ComboBox::from_label("Water bodies")
+                        .selected_text(water.bodies.label())
+                        .show_ui(ui, |ui| {
+                            for bodies in water::WaterBodies::ALL {
+                                ui.selectable_value(&mut water.bodies, bodies, bodies.label());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.label("Color");
+                        ui.color_edit_button_rgb(&mut water.color);
+                    });
+                    ui.add(egui::Slider::new(&mut water.opacity, 0.0..=1.0).text("Opacity"));
+                    ui.add(
+                        egui::Slider::new(&mut water.specular_strength, 0.0..=2.0)
+                            .text("Specular strength"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut water.shininess, 4.0..=512.0)
+                            .logarithmic(true)
+                            .text("Shininess"),
+                    );
+                    ui.add(
+                        egui::Slider::new(
+                            &mut water.resolution,
+                            water::MIN_RESOLUTION..=water::MAX_RESOLUTION,
+                        )
+                        .text("Resolution"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut water.edge_softness, 0.0..=6.0)
+                            .text("Shore softening"),
+                    );
+                    ui.add(egui::Slider::new(&mut water.depth_bias, 0..=64).text("Depth bias"));
+                    ui.add(
+                        egui::Slider::new(&mut water.depth_bias_slope, 0.0..=4.0)
+                            .text("Depth bias slope"),
+                    );
+                    ui.checkbox(&mut water.waves, "Waves");
+                    ui.add_enabled_ui(water.waves, |ui| {
+                        let [x, y] = water.wave_direction;
+                        let mut heading = y.atan2(x).to_degrees().rem_euclid(360.0);
+                        if ui
+                            .add(egui::Slider::new(&mut heading, 0.0..=360.0).text("Wave heading"))
+                            .changed()
+                        {
+                            let (sin, cos) = heading.to_radians().sin_cos();
+                            water.wave_direction = [cos, sin];
+                        }
+                        ui.add(
+                            egui::Slider::new(&mut water.wavelength, 1.0..=40.0)
+                                .logarithmic(true)
+                                .text("Wavelength"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut water.wave_amplitude, 0.0..=1.0)
+                                .text("Wave amplitude"),
+                        );
+                        ui.add(
+                            egui::Slider::new(&mut water.choppiness, 0.0..=1.0).text("Choppiness"),
+                        );
+                    });
+                    ui.separator();
+                    ui.label("Weather");
+                    ui.checkbox(&mut weather.enabled, "Rain and snow");
+                    ui.add_enabled_ui(weather.enabled, |ui| {
+                        ui.add(egui::Slider::new(&mut weather.density, 0.0..=1.0).text("Density"));
+                        ui.add(
+                            egui::Slider::new(
+                                &mut weather.extent,
+                                weather::MIN_EXTENT..=weather::MAX_EXTENT,
+                            )
+                            .text("Region size"),
+                        );
+                    });
+                    ui.separator();
+                    ui.label("Brush");
+                    egui::ComboBox::from_label("Mode")
+                        .selected_text(brush.mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in brush::BrushMode::ALL {
+                                ui.selectable_value(&mut brush.mode, mode, mode.label());
+                            }
+                        });
+                    ui.add(egui::Slider::new(&mut brush.radius, 1.0..=40.0).text("Radius"));
+                    ui.horizontal(|ui| {
+                        ui.label("Color");
+                        ui.color_edit_button_rgb(&mut brush.color);
+                    });
+                    ui.add(egui::Slider::new(&mut brush.opacity, 0.0..=1.0).text("Opacity"));
+                    ui.add_enabled(
+                        brush.mode == brush::BrushMode::Regenerate,
+                        egui::Slider::new(&mut brush.feather, 0.0..=brush.radius)
+                            .text("Regenerate feather"),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Clear paint").clicked() {
+                            clear_paint = true;
+                        }
+                        let undo_label = format!("Undo ({})", params.undo_len);
+                        if ui
+                            .add_enabled(params.undo_len > 0, egui::Button::new(undo_label))
+                            .clicked()
+                        {
+                            undo = true;
+                        }
+                        let redo_label = format!("Redo ({})", params.redo_len);
+                        if ui
+                            .add_enabled(params.redo_len > 0, egui::Button::new(redo_label))
+                            .clicked()
+                        {
+                            redo = true;
+                        }
+                    });
+                    ui.separator();
+                    ui.label("Measure");
+                    ui.checkbox(&mut measure.enabled, "Measure tool (T)");
+                    ui.checkbox(&mut measure.planet_units, "Planet units (km)");
+                    let distances = params.measure_overlay.as_ref().and_then(|o| o.distances);
+                    match distances {
+                        Some(distances) => {
+                            let radius = distances.radius;
+                            let straight = measure.format(distances.straight, radius);
+                            let surface = measure.format(distances.surface, radius);
+                            ui.label(format!("Straight: {straight}"));
+                            ui.label(format!("Surface: {surface}"));
+                        }
+                        None if params.measure_overlay.is_some() => {
+                            ui.label("Click a second point");
+                        }
+                        None => {
+                            ui.label("Click two points; right-click clears");
+                        }
+                    }
+                    if ui.button("Clear measurement").clicked() {
+                        clear_measure = true;
+                    }
+                    ui.separator();
+                    ui.label("Identify (I)");
+                    ui.label(match params.identified {
+                        Some(([x, y], Some(id))) => format!("({x}, {y}): object {id}"),
+                        Some(([x, y], None)) => format!("({x}, {y}): nothing"),
+                        None => "Press I over the scene".to_owned(),
+                    });
+                    ui.separator();
+                    ui.label("Rendering");
+                    egui::ComboBox::from_label("Depth")
+                        .selected_text(depth_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in depth::DepthMode::ALL {
+                                ui.selectable_value(&mut depth_mode, mode, mode.label());
+                            }
+                        });
+                    ui.checkbox(&mut horizon_cull, "Cull far side of globe");
+                    ui.checkbox(&mut double_buffer, "Double-buffer terrain vertices")
+                        .on_hover_text(
+                            "Smoother regenerates on large grids; doubles vertex memory",
+                        );
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut time_paused, "Pause time (Space)");
+                        if ui.button("Step (.)").clicked() {
+                            step_time = true;
+                        }
+                        ui.label(format!("{:.2} s", params.time));
+                    });
+                    let layers = ["Terrain", "Water"].into_iter().zip(params.draw_stats);
+                    for ((layer, stats), uploads) in layers.zip(params.uniform_uploads) {
+                        ui.label(format!(
+                            "{layer}: {} draws, {} / {} triangles, {uploads} uniform uploads",
+                            stats.draws, stats.triangles, stats.total_triangles
+                        ));
+                    }
+                    ui.add(
+                        egui::Slider::new(
+                            &mut pacing.max_frame_latency,
+                            pacing::MIN_FRAME_LATENCY..=pacing::MAX_FRAME_LATENCY,
+                        )
+                        .text("Max frame latency"),
+                    );
+                    ui.horizontal(|ui| {
+                        let mut capped = pacing.fps_cap.is_some();
+                        let mut fps = pacing.fps_cap.unwrap_or(pacing::DEFAULT_FPS_CAP);
+                        ui.checkbox(&mut capped, "Cap FPS");
+                        ui.add_enabled(capped, egui::Slider::new(&mut fps, 15..=240));
+                        pacing.fps_cap = capped.then_some(fps);
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut ui_scale, MIN_UI_SCALE..=MAX_UI_SCALE)
+                            .text("UI scale (Ctrl +/-)"),
+                    );
+                    ui.separator();
+                    ui.label("Stereo");
+                    ui.checkbox(&mut stereo.enabled, "Anaglyph (red/cyan)");
+                    ui.add(
+                        egui::Slider::new(&mut stereo.eye_separation, 0.0..=10.0)
+                            .text("Eye separation"),
+                    );
+                    ui.separator();
+                    ui.label("Export");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut export_path);
+                        if ui.button("Export CSV").clicked() {
+                            export_csv = Some(export_path.clone());
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut session_path);
+                        if ui.button("Save session").clicked() {
+                            save_session = Some(session_path.clone());
+                        }
+                        if ui.button("Load session").clicked() {
+                            load_session = Some(session_path.clone());
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut screenshot_path);
+                        if ui.button("Save PNG").clicked() {
+                            screenshot = Some((screenshot_path.clone(), screenshot_size));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Size");
+                        let [width, height] = &mut screenshot_size;
+                        ui.add(egui::DragValue::new(width).clamp_range(1..=screenshot::MAX_SIDE));
+                        ui.label("x");
+                        ui.add(egui::DragValue::new(height).clamp_range(1..=screenshot::MAX_SIDE));
+                        for scale in [1, 2, 4] {
+                            if ui.button(format!("{scale}x")).clicked() {
+                                screenshot_size = [surface_config.width, surface_config.height]
+                                    .map(|side| side.max(1) * scale);
+                            }
+                        }
+                    });
+                    ui.label("Turntable");
+                    if let Some(progress) = params.turntable {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::ProgressBar::new(progress).show_percentage());
+                            if ui.button("Cancel").clicked() {
+                                cancel_turntable = true;
+                            }
+                        });
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut turntable_path);
+                            if ui.button("Render frames").clicked() {
+                                turntable = Some((turntable_path.clone(), turntable_settings));
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Frames");
+                        let frames = &mut turntable_settings.frames;
+                        ui.add(egui::DragValue::new(frames).clamp_range(1..=3600));
+                        ui.label("Size");
+                        let [width, height] = &mut turntable_settings.size;
+                        ui.add(egui::DragValue::new(width).clamp_range(1..=screenshot::MAX_SIDE));
+                        ui.label("x");
+                        ui.add(egui::DragValue::new(height).clamp_range(1..=screenshot::MAX_SIDE));
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut turntable_settings.elevation_degrees, -80.0..=80.0)
+                            .text("Elevation (°)"),
+                    );
+                    ui.separator();
+                    egui::CollapsingHeader::new("About / Info").show(ui, |ui| {
+                        egui::Grid::new("system info")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for (label, value) in params.info.rows(surface_config) {
+                                    ui.label(label);
+                                    ui.label(value);
+                                    ui.end_row();
+                                }
+                            });
+                        if ui.button("Copy to clipboard").clicked() {
+                            let report = params.info.report(surface_config);
+                            ui.output_mut(|o| o.copied_text = report);
+                        }
+                    });
                 });
+            if let Some(texture_id) = params.viewport {
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none())
+                    .show(ctx, |ui| {
+                        viewport_layout = Some(viewport::show(ui, texture_id));
+                    });
+            }
+            if let Some(overlay) = &params.measure_overlay {
+                let clip = viewport_layout.map(|layout| layout.rect);
+                draw_measure_overlay(ctx, overlay, &measure, clip);
+            }
         });
+        self.smooth_passes = if reset {
+            terrain::DEFAULT_SMOOTH_PASSES
+        } else {
+            smooth_passes
+        };
+        self.export_path = export_path;
+        self.session_path = session_path;
+        self.screenshot_path = screenshot_path;
+        self.screenshot_size = screenshot_size;
+        self.turntable_path = turntable_path;
+        self.turntable = turntable_settings;
 
         self.state
             .handle_platform_output(window, full_output.platform_output);
@@ -500,53 +2331,668 @@ impl Gui {
         UiFrame {
             commands: user_cmd_bufs,
             randomize,
+            reset,
+            screenshot,
+            turntable,
+            cancel_turntable,
+            smooth,
+            export_csv,
+            save_session,
+            load_session,
             toggle_map,
+            map_auto_center,
+            map_reframe,
+            map_transition,
+            selected_terrain,
+            add_terrain,
+            remove_terrain,
+            transform,
             settings,
             settings_changed,
+            origin,
+            stereo,
+            sun,
+            detail,
+            water,
+            weather,
+            brush,
+            clear_paint,
+            undo,
+            redo,
+            measure,
+            clear_measure,
+            viewport: viewport_layout,
+            depth_mode,
+            horizon_cull,
+            double_buffer,
+            time_paused,
+            step_time,
+            pacing,
+            ui_scale,
         }
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let event_loop = EventLoop::new()?;
-    let mut state = pollster::block_on(State::new(&event_loop))?;
+/// Draws the measured path and its distance label behind the overlay window,
+/// clipped to the scene image when embedded.
+#[cfg(feature = "ui")]
+fn draw_measure_overlay(
+    ctx: &egui::Context,
+    overlay: &measure::MeasureOverlay,
+    settings: &measure::MeasureSettings,
+    clip: Option<egui::Rect>,
+) {
+    let mut painter = ctx.layer_painter(egui::LayerId::background());
+    if let Some(clip) = clip {
+        painter.set_clip_rect(clip);
+    }
+    let scale = ctx.pixels_per_point();
+    let points: Vec<Option<egui::Pos2>> = overlay
+        .screen_path
+        .iter()
+        .map(|p| p.map(|p| egui::pos2(p.x / scale, p.y / scale)))
+        .collect();
+    let color = egui::Color32::from_rgb(255, 220, 80);
+    let stroke = egui::Stroke::new(2.0, color);
+    for pair in points.windows(2) {
+        if let [Some(a), Some(b)] = pair {
+            painter.line_segment([*a, *b], stroke);
+        }
+    }
+    let ends = [points.first(), points.last()];
+    for end in ends.into_iter().flatten().flatten() {
+        painter.circle_filled(*end, 4.0, color);
+    }
+
+    let Some(distances) = overlay.distances else {
+        return;
+    };
+    let Some(anchor) = points[points.len() / 2] else {
+        return;
+    };
+    let text = format!(
+        "{} straight\n{} surface",
+        settings.format(distances.straight, distances.radius),
+        settings.format(distances.surface, distances.radius)
+    );
+    let galley = painter.layout_no_wrap(text, egui::FontId::proportional(14.0), color);
+    let rect = egui::Align2::CENTER_BOTTOM
+        .anchor_size(anchor - egui::vec2(0.0, 8.0), galley.size())
+        .expand(4.0);
+    painter.rect_filled(rect, 4.0, egui::Color32::from_black_alpha(170));
+    painter.galley(rect.shrink(4.0).min, galley, color);
+}
+
+/// Initial value of every setting the overlay exposes. `State::new` starts
+/// from it and "Reset to defaults" returns to it.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+struct Defaults {
+    map_auto_center: bool,
+    map_reframe: bool,
+    map_transition: transition::MapTransition,
+    terrain: terrain::TerrainSettings,
+    origin: glam::Vec2,
+    detail: detail::DetailSettings,
+    sun: sun::SunSettings,
+    water: water::WaterSettings,
+    weather: weather::WeatherSettings,
+    brush: brush::BrushSettings,
+    measure: measure::MeasureSettings,
+    stereo: stereo::StereoSettings,
+    depth_mode: depth::DepthMode,
+    horizon_cull: bool,
+    double_buffer: bool,
+    /// Taken from the launch options rather than `FramePacing::default`.
+    pacing: pacing::FramePacing,
+    #[cfg(feature = "ui")]
+    ui_scale: f32,
+}
+
+impl Defaults {
+    fn new(options: &LaunchOptions) -> Self {
+        Self {
+            map_auto_center: true,
+            map_reframe: true,
+            map_transition: options.world.map_transition,
+            terrain: terrain::TerrainSettings::default(),
+            origin: glam::Vec2::ZERO,
+            detail: detail::DetailSettings::default(),
+            sun: sun::SunSettings::default(),
+            water: water::WaterSettings::default(),
+            weather: weather::WeatherSettings::default(),
+            brush: brush::BrushSettings::default(),
+            measure: measure::MeasureSettings::default(),
+            stereo: stereo::StereoSettings::default(),
+            depth_mode: depth::DepthMode::Standard,
+            horizon_cull: options.horizon_cull,
+            double_buffer: options.double_buffer,
+            pacing: options.pacing,
+            #[cfg(feature = "ui")]
+            ui_scale: DEFAULT_UI_SCALE,
+        }
+    }
+}
+
+/// Startup configuration, taken by `State::new` and `State::headless` so an
+/// embedding host can set it directly.
+#[derive(Clone, Debug, Default)]
+struct LaunchOptions {
+    camera: input::CameraStart,
+    world: world::WorldConfig,
+    /// Paints the base terrain on startup.
+    session: Option<session::Session>,
+    pacing: pacing::FramePacing,
+    /// Start with far-side culling on.
+    horizon_cull: bool,
+    /// Start with the spare terrain vertex buffer allocated.
+    double_buffer: bool,
+    /// Render a turntable into `turntable::DEFAULT_DIRECTORY` on startup.
+    turntable: Option<turntable::TurntableSettings>,
+    /// Clear to transparent and ask the compositor to blend the window.
+    transparent: bool,
+    /// Print the adapter and surface report, as the overlay's Info section
+    /// shows it, on startup.
+    info: bool,
+    /// Render the scene into an egui image instead of straight to the window.
+    #[cfg(feature = "ui")]
+    embedded: bool,
+}
+
+/// Reads `--world=PATH`, `--session=PATH`, `--camera=x,y,z`, `--target=x,y,z`,
+/// `--radius=R`, `--flatten-seconds=S`, `--frame-latency=1..3`, `--fps-cap[=N]`,
+/// `--horizon-cull`, `--double-buffer`, `--info`,
+/// `--turntable[=FRAMES[,WIDTHxHEIGHT[,ELEVATION]]]`,
+/// `--transparent` and `--embedded` (ui builds only) from the command line,
+/// falling back to the defaults for anything missing and rejecting anything
+/// else. A bare `--fps-cap` caps at 60. The world file is read first, wherever it appears, so
+/// `--radius` and `--flatten-seconds` override it.
+fn options_from_args() -> Result<LaunchOptions, Box<dyn Error>> {
+    let mut options = LaunchOptions::default();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--world=")) {
+        options.world = world::WorldConfig::load(Path::new(path))?;
+    }
+    for arg in args {
+        if arg.starts_with("--world=") {
+            continue;
+        } else if let Some(path) = arg.strip_prefix("--session=") {
+            options.session = Some(session::Session::load(Path::new(path))?);
+        } else if let Some(value) = arg.strip_prefix("--camera=") {
+            options.camera.position = Some(parse_vec3(value)?);
+        } else if let Some(value) = arg.strip_prefix("--target=") {
+            options.camera.target = parse_vec3(value)?;
+        } else if let Some(value) = arg.strip_prefix("--radius=") {
+            let radius = value.parse::<f32>()?;
+            if !(radius.is_finite() && radius > 0.0) {
+                return Err(format!("--radius must be positive but got {radius}").into());
+            }
+            options.world.radius = radius;
+        } else if let Some(value) = arg.strip_prefix("--flatten-seconds=") {
+            let seconds = value.parse::<f32>()?;
+            if !(seconds.is_finite() && seconds > 0.0) {
+                return Err(format!("--flatten-seconds must be positive but got {seconds}").into());
+            }
+            options.world.map_transition = transition::MapTransition::Fixed { seconds };
+        } else if let Some(value) = arg.strip_prefix("--frame-latency=") {
+            let latency = value.parse::<u32>()?;
+            if !(pacing::MIN_FRAME_LATENCY..=pacing::MAX_FRAME_LATENCY).contains(&latency) {
+                return Err(format!("--frame-latency must be 1-3 but got {latency}").into());
+            }
+            options.pacing.max_frame_latency = latency;
+        } else if arg == "--fps-cap" {
+            options.pacing.fps_cap = Some(pacing::DEFAULT_FPS_CAP);
+        } else if let Some(value) = arg.strip_prefix("--fps-cap=") {
+            options.pacing.fps_cap = Some(value.parse::<u32>()?).filter(|&fps| fps > 0);
+        } else if arg == "--horizon-cull" {
+            options.horizon_cull = true;
+        } else if arg == "--double-buffer" {
+            options.double_buffer = true;
+        } else if arg == "--turntable" {
+            options.turntable = Some(turntable::TurntableSettings::default());
+        } else if let Some(value) = arg.strip_prefix("--turntable=") {
+            options.turntable = Some(turntable::TurntableSettings::parse(value)?);
+        } else if arg == "--transparent" {
+            options.transparent = true;
+        } else if arg == "--info" {
+            options.info = true;
+        } else if arg == "--embedded" {
+            #[cfg(feature = "ui")]
+            {
+                options.embedded = true;
+            }
+            #[cfg(not(feature = "ui"))]
+            return Err("--embedded needs a build with the `ui` feature".into());
+        } else {
+            return Err(format!("unknown argument `{arg}`").into());
+        }
+    }
+    Ok(options)
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3, Box<dyn Error>> {
+    let parts = value
+        .split(',')
+        .map(|p| p.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    match parts[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("expected x,y,z but got `{value}`").into()),
+    }
+}
+
+/// Begins a pass that draws over the terrain pass's color and depth without
+/// clearing either, for the translucent layers.
+fn begin_overlay_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    label: &str,
+    view: &'a wgpu::TextureView,
+    depth: &'a wgpu::TextureView,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: depth,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }),
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    })
+}
+
+/// Clears `view` in a pass of its own.
+#[cfg(feature = "ui")]
+fn clear_target(encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, color: wgpu::Color) {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("clear pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(color),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // Wait for submitted work so the buffers, textures and pipelines are
+        // freed as their handles drop below rather than at some later poll.
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+}
+
+/// Opens the editor and runs until its window is closed.
+///
+/// The state is dropped on `LoopExiting`, while the event loop still owns the
+/// window, so GPU resources and the window are released before this returns
+/// and the same event loop can be run again to reopen the editor.
+fn run(event_loop: &mut EventLoop<()>, options: LaunchOptions) -> Result<(), Box<dyn Error>> {
+    let mut state = Some(pollster::block_on(State::new(event_loop, options))?);
 
-    event_loop.run(move |event, elwt| {
+    event_loop.run_on_demand(move |event, elwt| {
+        if let Event::LoopExiting = event {
+            state = None;
+            return;
+        }
+        let Some(state) = state.as_mut() else {
+            return;
+        };
         match event {
-            Event::DeviceEvent { event, .. } => {
-                if let DeviceEvent::MouseMotion { delta } = event {
-                    state.input.handle_mouse_delta(delta);
-                }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                state.input.handle_mouse_delta(delta);
             }
             Event::WindowEvent { event, window_id }
-                if window_id == state.window().id() =>
+                if state.window().map(Window::id) == Some(window_id) && !state.input(&event) =>
             {
-                if !state.input(&event) {
-                    match event {
-                        WindowEvent::CloseRequested => elwt.exit(),
-                        WindowEvent::Resized(size) => state.resize(size),
-                        WindowEvent::ScaleFactorChanged { .. } => {
-                            state.resize(state.window().inner_size());
+                match event {
+                    WindowEvent::CloseRequested => elwt.exit(),
+                    WindowEvent::Resized(size) => state.resize(size),
+                    WindowEvent::ScaleFactorChanged { .. } => {
+                        if let Some(size) = state.window().map(Window::inner_size) {
+                            state.resize(size);
                         }
-                        WindowEvent::RedrawRequested => {
-                            state.update();
-                            match state.render() {
-                                Ok(_) => {}
-                                Err(SurfaceError::Lost | SurfaceError::Outdated) => {
-                                    state.resize(state.size)
-                                }
-                                Err(SurfaceError::OutOfMemory) => elwt.exit(),
-                                Err(e) => eprintln!("render error: {e:?}"),
+                    }
+                    WindowEvent::RedrawRequested => {
+                        state.update();
+                        match state.render() {
+                            Ok(_) => {}
+                            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                                state.resize(state.size)
                             }
+                            Err(SurfaceError::OutOfMemory) => elwt.exit(),
+                            Err(e) => eprintln!("render error: {e:?}"),
                         }
-                        _ => {}
                     }
+                    _ => {}
+                }
+            }
+            Event::AboutToWait => {
+                state.pace_frame();
+                if let Some(window) = state.window() {
+                    window.request_redraw();
                 }
             }
-            Event::AboutToWait => state.window().request_redraw(),
             _ => {}
         }
     })?;
 
     Ok(())
 }
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = options_from_args()?;
+    let mut event_loop = EventLoop::new()?;
+    run(&mut event_loop, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: PhysicalSize<u32> = PhysicalSize::new(64, 48);
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// A headless state on the fallback (software) adapter, or `None` when
+    /// this machine has none, in which case the test is skipped.
+    fn headless_state() -> Option<State> {
+        headless_state_with(LaunchOptions::default())
+    }
+
+    fn headless_state_with(options: LaunchOptions) -> Option<State> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            force_fallback_adapter: true,
+            ..Default::default()
+        }));
+        let Some(adapter) = adapter else {
+            eprintln!("no fallback adapter, skipping");
+            return None;
+        };
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&Default::default(), None)).ok()?;
+        Some(State::headless(device, queue, FORMAT, SIZE, options))
+    }
+
+    fn render_target(state: &State) -> wgpu::Texture {
+        state.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("test target"),
+            size: wgpu::Extent3d {
+                width: SIZE.width,
+                height: SIZE.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+    }
+
+    #[test]
+    fn tick_settles_the_map_transition_and_renders() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        state.toggle_map();
+        for _ in 0..600 {
+            state.tick(1.0 / 60.0);
+        }
+        assert_eq!(state.map_blend, 1.0);
+
+        let target = render_target(&state);
+        state.render_to(&target.create_view(&wgpu::TextureViewDescriptor::default()));
+    }
+
+    #[test]
+    fn headless_state_can_be_created_and_dropped_repeatedly() {
+        for _ in 0..16 {
+            let Some(mut state) = headless_state() else {
+                return;
+            };
+            state.tick(1.0 / 60.0);
+            drop(state);
+        }
+    }
+
+    #[test]
+    fn tiles_get_their_own_object_ids() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        state.add_terrain();
+        state.add_terrain();
+        let ids: Vec<u32> = state.terrains.iter().map(terrain::Terrain::id).collect();
+        assert_eq!(ids, [terrain::TERRAIN_ID, 2, 3]);
+
+        // The base globe fills the middle of the default view, until the
+        // second tile is moved over it.
+        let [x, y] = [SIZE.width / 2, SIZE.height / 2];
+        state.tick(1.0 / 60.0);
+        assert_eq!(state.pick_at(x, y), Some(terrain::TERRAIN_ID));
+        let mut transform = state.terrains[2].transform();
+        transform.offset = Vec3::ZERO;
+        transform.scale = 1.5;
+        state.terrains[2].set_transform(transform);
+        state.tick(1.0 / 60.0);
+        assert_eq!(state.pick_at(x, y), Some(3));
+        state.cursor = glam::Vec2::new(x as f32, y as f32);
+        state.identify();
+        assert_eq!(state.identified, Some(([x, y], Some(3))));
+    }
+
+    #[test]
+    fn a_session_repaints_the_selected_terrain_as_one_undo_step() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        let grid = state.terrain().grid();
+        let texels = vec![[0.2, 0.4, 0.6, 1.0]; grid.points()];
+        let paint = session::PaintLayer {
+            grid,
+            texels: texels.clone(),
+        };
+        let loaded = session::Session {
+            paint: Some(paint),
+            ui_scale: cfg!(feature = "ui").then_some(1.5),
+        };
+        state.apply_session(loaded.clone()).unwrap();
+        assert_eq!(state.terrain().paint_layer(), texels);
+        assert_eq!(state.session(), loaded);
+        state.undo_paint();
+        assert!(state.terrain().paint_layer().iter().all(|&t| t == [0.0; 4]));
+
+        let paint = session::PaintLayer {
+            grid: terrain::Grid::new(grid.lat + 1),
+            texels: vec![[1.0; 4]; terrain::Grid::new(grid.lat + 1).points()],
+        };
+        let session = session::Session {
+            paint: Some(paint),
+            ui_scale: None,
+        };
+        assert!(state.apply_session(session).is_err());
+        assert_eq!(state.paint_history.undo_len(), 0);
+    }
+
+    #[cfg(feature = "ui")]
+    #[test]
+    fn reset_keeps_the_ui_scale_a_session_loaded() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        let session = session::Session {
+            paint: None,
+            ui_scale: Some(1.5),
+        };
+        state.apply_session(session).unwrap();
+        state.ui_scale = MAX_UI_SCALE;
+        state.reset_settings();
+        assert_eq!(state.ui_scale, 1.5);
+        assert_eq!(state.session().ui_scale, Some(1.5));
+    }
+
+    #[test]
+    fn bounds_hold_a_moved_and_scaled_surface_through_the_morph() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        let transform = terrain::TerrainTransform {
+            offset: Vec3::new(30.0, -4.0, 12.5),
+            scale: 0.6,
+        };
+        state.terrains[0].set_transform(transform);
+        let terrain = state.base_terrain();
+        for morph in [0.0, 0.3, 0.5, 1.0] {
+            let (min, max) = terrain.bounds(morph);
+            let slack = Vec3::splat(1e-3);
+            for (u, v) in (0..=24).flat_map(|u| (0..=12).map(move |v| (u, v))) {
+                let uv = glam::Vec2::new(u as f32 / 24.0, v as f32 / 12.0);
+                let point = terrain.surface_point(uv, morph, 0.7);
+                let inside = point.cmpge(min - slack).all() && point.cmple(max + slack).all();
+                assert!(inside, "{point} outside {min}..{max} at morph {morph}");
+            }
+        }
+        // On the map the box is the map's footprint, scaled and moved.
+        let (min, max) = terrain.bounds(1.0);
+        let map = terrain::map_size(terrain.radius()) * transform.scale;
+        assert!(((max - min).x - map.x).abs() < 1e-3);
+        assert!(((min + max).z * 0.5 - transform.offset.z).abs() < 1e-3);
+    }
+
+    #[test]
+    fn an_unchanged_view_uploads_no_uniforms() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        state.time_paused = true;
+        let uploads = |state: &State| {
+            let terrain = state.base_terrain().uniform_uploads();
+            [terrain, state.water.uniform_uploads()]
+        };
+        state.tick(1.0 / 60.0);
+        let settled = uploads(&state);
+        assert!(settled.iter().all(|&count| count > 0));
+        for _ in 0..10 {
+            state.tick(1.0 / 60.0);
+        }
+        assert_eq!(uploads(&state), settled);
+
+        // A change to anything in the terrain's uniform is written once.
+        let mut settings = state.base_terrain().settings();
+        settings.biome_blend *= 0.5;
+        state.terrains[0].set_settings(settings);
+        for _ in 0..10 {
+            state.tick(1.0 / 60.0);
+        }
+        assert_eq!(uploads(&state), [settled[0] + 1, settled[1]]);
+    }
+
+    #[test]
+    fn a_brush_stroke_is_one_undo_step() {
+        let Some(mut state) = headless_state() else {
+            return;
+        };
+        let unpainted = state.terrain().paint_layer();
+        state.brush.mode = brush::BrushMode::Paint;
+        state.cursor = glam::Vec2::new(SIZE.width as f32, SIZE.height as f32) * 0.5;
+        state.tick(1.0 / 60.0);
+        state.input.begin_stroke();
+        for step in 0..5 {
+            state.cursor.x += step as f32;
+            state.tick(1.0 / 60.0);
+        }
+        assert_eq!(state.paint_history.undo_len(), 0, "committed mid-stroke");
+        state.input.end_stroke();
+        state.tick(1.0 / 60.0);
+        assert_eq!(state.paint_history.undo_len(), 1);
+        let painted = state.terrain().paint_layer();
+        assert_ne!(painted, unpainted);
+
+        state.undo_paint();
+        assert_eq!(state.terrain().paint_layer(), unpainted);
+        state.redo_paint();
+        assert_eq!(state.terrain().paint_layer(), painted);
+        assert_eq!(state.paint_history.undo_len(), 1);
+    }
+
+    /// Times the frame that uploads a randomized grid, from `tick` through its
+    /// render and `device.poll`, against idle frames after it, with the spare
+    /// vertex buffer off and on. Frames while the grid generates are left
+    /// out, as they share the CPU with the generation thread. Run with
+    /// `cargo test --release whole_grid_upload_timings -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "timing run rather than a check"]
+    fn whole_grid_upload_timings() {
+        let median = |times: &mut Vec<f64>| {
+            times.sort_by(f64::total_cmp);
+            times[times.len() / 2]
+        };
+        for rows in [512, 1024] {
+            for double_buffer in [false, true] {
+                let world = world::WorldConfig {
+                    grid: terrain::Grid::new(rows),
+                    ..world::WorldConfig::default()
+                };
+                let options = LaunchOptions {
+                    world,
+                    double_buffer,
+                    ..LaunchOptions::default()
+                };
+                let Some(mut state) = headless_state_with(options) else {
+                    return;
+                };
+                let target = render_target(&state);
+                let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+                // Leaves a frame in flight, as the window loop does, then
+                // times the next one.
+                let frame = |state: &mut State| {
+                    state.render_to(&view);
+                    let start = Instant::now();
+                    state.tick(1.0 / 60.0);
+                    state.render_to(&view);
+                    state.device.poll(wgpu::Maintain::Wait);
+                    start.elapsed().as_secs_f64() * 1000.0
+                };
+                let (mut uploads, mut idle) = (Vec::new(), Vec::new());
+                for _ in 0..5 {
+                    state.randomize_terrain();
+                    loop {
+                        let elapsed = frame(&mut state);
+                        if state.terrain().generation_progress().is_none() {
+                            uploads.push(elapsed);
+                            break;
+                        }
+                    }
+                    idle.extend((0..3).map(|_| frame(&mut state)));
+                }
+                let (upload, idle) = (median(&mut uploads), median(&mut idle));
+                println!(
+                    "grid {rows}, spare {double_buffer}: upload frame {upload:.1} ms, \
+                     idle frame {idle:.1} ms"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+
+/// Segments used to follow the surface between the two measured points.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const PATH_SAMPLES: usize = 96;
+/// Planet units treat the globe's radius as Earth's mean radius.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+const PLANET_RADIUS_KM: f32 = 6371.0;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MeasureSettings {
+    /// While enabled, left clicks place measure points instead of steering
+    /// the camera or painting.
+    pub enabled: bool,
+    /// Report distances in kilometres on an Earth-sized planet.
+    pub planet_units: bool,
+}
+
+impl MeasureSettings {
+    /// Formats a world-space `distance` measured on a globe of `radius`.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn format(&self, distance: f32, radius: f32) -> String {
+        if self.planet_units {
+            format!("{:.0} km", distance / radius * PLANET_RADIUS_KM)
+        } else {
+            format!("{distance:.2} units")
+        }
+    }
+}
+
+/// Up to two picked map coordinates. Picking again after both are set starts
+/// a new measurement.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Measurement {
+    start: Option<Vec2>,
+    end: Option<Vec2>,
+}
+
+impl Measurement {
+    pub fn add(&mut self, uv: Vec2) {
+        if self.start.is_some() && self.end.is_none() {
+            self.end = Some(uv);
+        } else {
+            self.start = Some(uv);
+            self.end = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn start(&self) -> Option<Vec2> {
+        self.start
+    }
+
+    /// Both endpoints, once the second one has been picked.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn endpoints(&self) -> Option<(Vec2, Vec2)> {
+        Some((self.start?, self.end?))
+    }
+}
+
+/// Distances between two surface points, in world units.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Distances {
+    /// Straight line through space (through the planet, on the globe).
+    pub straight: f32,
+    /// Along the sampled surface path, following terrain heights.
+    pub surface: f32,
+    /// Radius of the globe the path lies on, for planet units.
+    pub radius: f32,
+}
+
+impl Distances {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn of_path(path: &[Vec3], radius: f32) -> Self {
+        let straight = match (path.first(), path.last()) {
+            (Some(a), Some(b)) => a.distance(*b),
+            _ => 0.0,
+        };
+        let surface = path.windows(2).map(|w| w[0].distance(w[1])).sum();
+        Self {
+            straight,
+            surface,
+            radius,
+        }
+    }
+}
+
+/// A measurement as seen from the current camera, for the overlay.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub struct MeasureOverlay {
+    /// Surface path in physical pixels; `None` where a sample is off screen
+    /// or behind the globe.
+    pub screen_path: Vec<Option<Vec2>>,
+    /// Set once both endpoints are picked.
+    pub distances: Option<Distances>,
+}
+
+/// Projects a world point to physical pixels, or `None` behind the camera.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub fn project(view_proj: Mat4, point: Vec3, width: u32, height: u32) -> Option<Vec2> {
+    let clip = view_proj * point.extend(1.0);
+    if clip.w <= 1e-4 {
+        return None;
+    }
+    let ndc = clip.xy() / clip.w;
+    Some(Vec2::new(
+        (ndc.x + 1.0) * 0.5 * width as f32,
+        (1.0 - ndc.y) * 0.5 * height as f32,
+    ))
+}
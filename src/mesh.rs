@@ -0,0 +1,288 @@
+use glam::Vec3;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+/// Draw calls and triangles a layer submitted for its last view.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draws: u32,
+    pub triangles: u32,
+    /// Triangles in the whole mesh, drawn when nothing is culled.
+    pub total_triangles: u32,
+}
+
+impl DrawStats {
+    /// Stats for drawing `ranges` of an `index_count` mesh, or all of it.
+    pub fn new(ranges: Option<&[Range<u32>]>, index_count: u32) -> Self {
+        let (draws, indices) = match ranges {
+            Some(ranges) => (
+                ranges.len() as u32,
+                ranges.iter().map(|range| range.len() as u32).sum(),
+            ),
+            None => (1, index_count),
+        };
+        Self {
+            draws,
+            triangles: indices / 3,
+            total_triangles: index_count / 3,
+        }
+    }
+}
+
+/// Narrowest index format that can address `vertex_count` vertices. Triangle
+/// lists have no primitive restart, so Uint16 covers all 65536 indices.
+pub fn index_format(vertex_count: usize) -> wgpu::IndexFormat {
+    if vertex_count <= u16::MAX as usize + 1 {
+        wgpu::IndexFormat::Uint16
+    } else {
+        wgpu::IndexFormat::Uint32
+    }
+}
+
+/// Triangle-list index buffer stored as u16 when the mesh is small enough,
+/// halving its memory and bandwidth, and as u32 otherwise.
+pub struct IndexBuffer {
+    buffer: wgpu::Buffer,
+    format: wgpu::IndexFormat,
+    count: u32,
+}
+
+impl IndexBuffer {
+    pub fn new(device: &wgpu::Device, label: &str, indices: &[u32], vertex_count: usize) -> Self {
+        let format = index_format(vertex_count);
+        let contents = match format {
+            wgpu::IndexFormat::Uint16 => {
+                let narrow: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+                bytemuck::cast_slice(&narrow).to_vec()
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: &contents,
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            buffer,
+            format,
+            count: indices.len() as u32,
+        }
+    }
+
+    /// Binds the buffer and draws every index with the pass's current state.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_index_buffer(self.buffer.slice(..), self.format);
+        pass.draw_indexed(0..self.count, 0, 0..1);
+    }
+
+    /// Like `draw`, but only `ranges` of the indices (everything for `None`),
+    /// one draw call per range.
+    pub fn draw_ranges<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        ranges: Option<&[Range<u32>]>,
+    ) {
+        let Some(ranges) = ranges else {
+            return self.draw(pass);
+        };
+        pass.set_index_buffer(self.buffer.slice(..), self.format);
+        for range in ranges {
+            pass.draw_indexed(range.clone(), 0, 0..1);
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Index ranges of a `rows` x `columns` quad grid wrapped around a sphere
+/// whose quads may lie within `reach` radians of the point below `eye`, or
+/// `None` if every quad may. The grid is laid out like the terrain and water
+/// spheres: six indices per quad, rows from the north pole (+Y) to the south
+/// pole, columns eastward from +X towards +Z. Each row contributes at most
+/// two ranges, and neighbouring ranges are merged.
+pub fn horizon_ranges(rows: u32, columns: u32, eye: Vec3, reach: f32) -> Option<Vec<Range<u32>>> {
+    if reach >= PI || eye.length_squared() == 0.0 {
+        return None;
+    }
+    let eye = eye.normalize();
+    let (sin_eye, cos_eye) = eye.y.clamp(-1.0, 1.0).acos().sin_cos();
+    let eye_lon = eye.z.atan2(eye.x);
+    let row_step = PI / rows as f32;
+    let column_step = TAU / columns as f32;
+
+    let mut ranges: Vec<Range<u32>> = Vec::new();
+    let mut push = |first: u32, end: u32| {
+        let (start, end) = (first * 6, end * 6);
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    };
+    for row in 0..rows {
+        let base = row * columns;
+        let top = row as f32 * row_step;
+        let bottom = top + row_step;
+        let center = top + row_step * 0.5;
+        let widest = if top <= FRAC_PI_2 && bottom >= FRAC_PI_2 {
+            1.0
+        } else {
+            top.sin().max(bottom.sin())
+        };
+        // Every point of a quad lies within this angle of the quad's center:
+        // half a row along the meridian, then at most half a column along
+        // the row's widest parallel.
+        let limit = reach + 0.5 * (row_step + widest * column_step);
+        if limit >= PI {
+            push(base, base + columns);
+            continue;
+        }
+        // A quad center at longitude offset d is within `limit` when
+        // cos(center) cos(eye) + sin(center) sin(eye) cos(d) >= cos(limit).
+        let (sin_center, cos_center) = center.sin_cos();
+        let needed = limit.cos() - cos_center * cos_eye;
+        let across = sin_center * sin_eye;
+        let half_width = if across <= 1e-6 {
+            if needed > 0.0 {
+                continue;
+            }
+            PI
+        } else {
+            let cos_width = needed / across;
+            if cos_width > 1.0 {
+                continue;
+            }
+            cos_width.max(-1.0).acos()
+        };
+        // Columns whose centers, at (x + 0.5) * column_step, fall in the arc.
+        let first = ((eye_lon - half_width) / column_step - 0.5).ceil() as i64;
+        let last = ((eye_lon + half_width) / column_step - 0.5).floor() as i64;
+        let count = last - first + 1;
+        if count >= columns as i64 {
+            push(base, base + columns);
+            continue;
+        }
+        if count <= 0 {
+            continue;
+        }
+        let first = first.rem_euclid(columns as i64) as u32;
+        let end = first + count as u32;
+        if end <= columns {
+            push(base + first, base + end);
+        } else {
+            push(base, base + end - columns);
+            push(base + first, base + columns);
+        }
+    }
+    Some(ranges)
+}
+
+/// Debug-build check that a mesh survives back-face culling: every triangle
+/// must wind counter-clockwise (wgpu's default front face) when seen from the
+/// side `outward` points to at its centroid. Slivers whose edges are within
+/// rounding of parallel, such as the ones collapsed into a pole, are skipped.
+pub fn debug_assert_winding(
+    label: &str,
+    positions: &[Vec3],
+    indices: &[u32],
+    outward: impl Fn(Vec3) -> Vec3,
+) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        let [a, b, c] = [0, 1, 2].map(|k| positions[corners[k] as usize]);
+        let (ab, ac) = (b - a, c - a);
+        let normal = ab.cross(ac);
+        if normal.length_squared() <= 1e-8 * ab.length_squared() * ac.length_squared() {
+            continue;
+        }
+        assert!(
+            normal.dot(outward((a + b + c) / 3.0)) > 0.0,
+            "{label}: triangle {triangle} winds clockwise seen from outside, so back-face culling hides it"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Grid;
+    use wgpu::IndexFormat::{Uint16, Uint32};
+
+    /// Every quad some point of which lies within `reach` of the point below
+    /// `eye`, found by sampling each quad densely.
+    fn quads_in_reach(rows: u32, columns: u32, eye: Vec3, reach: f32) -> Vec<u32> {
+        const SAMPLES: u32 = 8;
+        let eye = eye.normalize();
+        let step = |cell: u32, sample: u32| cell as f32 + sample as f32 / SAMPLES as f32;
+        let mut quads = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let seen = (0..=SAMPLES).any(|i| {
+                    (0..=SAMPLES).any(|j| {
+                        let lat = step(row, i) * PI / rows as f32;
+                        let lon = step(column, j) * TAU / columns as f32;
+                        let (sin_lat, cos_lat) = lat.sin_cos();
+                        let dir = Vec3::new(lon.cos() * sin_lat, cos_lat, lon.sin() * sin_lat);
+                        dir.dot(eye).clamp(-1.0, 1.0).acos() <= reach
+                    })
+                });
+                if seen {
+                    quads.push(row * columns + column);
+                }
+            }
+        }
+        quads
+    }
+
+    #[test]
+    fn horizon_ranges_keep_every_quad_in_reach() {
+        let (rows, columns) = (24, 32);
+        let eyes = [
+            Vec3::X,
+            Vec3::new(-1.0, 0.0, 0.01),
+            Vec3::new(-1.0, 0.0, -0.01),
+            Vec3::new(0.3, 0.8, -0.5),
+            Vec3::new(-0.2, -0.9, 0.4),
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::new(0.01, 1.0, 0.0),
+        ];
+        for eye in eyes {
+            for reach in [0.05, 0.3, 0.8, 1.4, FRAC_PI_2, 2.2, 3.0] {
+                let ranges = horizon_ranges(rows, columns, eye, reach).unwrap();
+                for pair in ranges.windows(2) {
+                    assert!(pair[0].end < pair[1].start, "{ranges:?} overlap or touch");
+                }
+                let kept = |quad: u32| ranges.iter().any(|range| range.contains(&(quad * 6)));
+                for range in &ranges {
+                    assert!(range.start % 6 == 0 && range.end % 6 == 0);
+                    assert!(range.end <= rows * columns * 6);
+                }
+                for quad in quads_in_reach(rows, columns, eye, reach) {
+                    assert!(kept(quad), "eye {eye}, reach {reach}: quad {quad} dropped");
+                }
+                // Conservative, but still culling well away from the horizon.
+                let drawn: u32 = ranges.iter().map(|range| range.len() as u32 / 6).sum();
+                if reach <= 0.8 {
+                    assert!(drawn < rows * columns / 2, "eye {eye}, reach {reach}");
+                }
+            }
+        }
+        assert_eq!(horizon_ranges(rows, columns, Vec3::X, PI), None);
+        assert_eq!(horizon_ranges(rows, columns, Vec3::ZERO, 1.0), None);
+    }
+
+    #[test]
+    fn index_format_boundary() {
+        assert_eq!(Grid::new(255).points(), 65280);
+        assert_eq!(Grid::new(256).points(), 65792);
+        assert_eq!(index_format(Grid::new(255).points()), Uint16);
+        assert_eq!(index_format(65536), Uint16);
+        assert_eq!(index_format(65537), Uint32);
+        assert_eq!(index_format(Grid::new(256).points()), Uint32);
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+pub const MIN_FRAME_LATENCY: u32 = 1;
+pub const MAX_FRAME_LATENCY: u32 = 3;
+const DEFAULT_FRAME_LATENCY: u32 = 2;
+pub const DEFAULT_FPS_CAP: u32 = 60;
+
+/// Swapchain latency and an optional frame-rate cap.
+///
+/// The cap is applied by sleeping on the CPU, so it works with any present
+/// mode; it bounds the frame rate from above but cannot raise it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FramePacing {
+    /// Frames the presentation engine may queue ahead; 1 gives the lowest
+    /// input latency at some cost in throughput.
+    pub max_frame_latency: u32,
+    /// Frames per second to stay under; `None` renders as fast as the present
+    /// mode allows.
+    pub fps_cap: Option<u32>,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        Self {
+            max_frame_latency: DEFAULT_FRAME_LATENCY,
+            fps_cap: None,
+        }
+    }
+}
+
+impl FramePacing {
+    pub fn frame_latency(&self) -> u32 {
+        self.max_frame_latency
+            .clamp(MIN_FRAME_LATENCY, MAX_FRAME_LATENCY)
+    }
+
+    /// Sleeps out whatever is left of the capped frame interval since
+    /// `frame_start`.
+    pub fn wait(&self, frame_start: Instant) {
+        let Some(fps) = self.fps_cap.filter(|&fps| fps > 0) else {
+            return;
+        };
+        let interval = Duration::from_secs_f64(1.0 / fps as f64);
+        if let Some(remaining) = interval.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+}
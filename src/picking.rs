@@ -0,0 +1,54 @@
+use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub dir: Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.dir * t
+    }
+}
+
+/// World-space ray from `eye` through a cursor position given in physical
+/// pixels. Independent of the depth convention baked into `view_proj`.
+pub fn cursor_ray(view_proj: Mat4, eye: Vec3, cursor: Vec2, width: u32, height: u32) -> Ray {
+    let ndc = Vec2::new(
+        cursor.x / width.max(1) as f32 * 2.0 - 1.0,
+        1.0 - cursor.y / height.max(1) as f32 * 2.0,
+    );
+    let point = view_proj.inverse() * ndc.extend(0.5).extend(1.0);
+    let point = point.xyz() / point.w;
+    Ray {
+        origin: eye,
+        dir: (point - eye).normalize_or_zero(),
+    }
+}
+
+/// Nearest positive hit distance of `ray` against a sphere at the origin.
+pub fn intersect_sphere(ray: &Ray, radius: f32) -> Option<f32> {
+    let b = ray.origin.dot(ray.dir);
+    let c = ray.origin.length_squared() - radius * radius;
+    let disc = b * b - c;
+    if disc < 0.0 {
+        return None;
+    }
+    let root = disc.sqrt();
+    let t = if -b - root > 0.0 {
+        -b - root
+    } else {
+        -b + root
+    };
+    (t > 0.0).then_some(t)
+}
+
+/// Hit distance of `ray` against the horizontal plane `y = height`.
+pub fn intersect_plane(ray: &Ray, height: f32) -> Option<f32> {
+    if ray.dir.y.abs() < 1e-6 {
+        return None;
+    }
+    let t = (height - ray.origin.y) / ray.dir.y;
+    (t > 0.0).then_some(t)
+}
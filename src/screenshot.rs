@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::depth::DepthTexture;
+
+/// Output size of the P shortcut, as a multiple of the scene size.
+pub const DEFAULT_SCALE: u32 = 2;
+/// Largest side offered in the overlay; the device limit still applies.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const MAX_SIDE: u32 = 16384;
+pub const DEFAULT_PATH: &str = "screenshot.png";
+
+/// Offscreen color and depth targets for a single capture, sized
+/// independently of the swapchain.
+pub struct Capture {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub depth: DepthTexture,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+}
+
+impl Capture {
+    /// Allocates targets matching `config`'s size and format. Only 8-bit RGBA
+    /// and BGRA formats can be saved.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<Self, Box<dyn Error>> {
+        swaps_red_blue(config.format)?;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("screenshot target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(Self {
+            texture,
+            view,
+            depth: DepthTexture::new(device, config),
+            format: config.format,
+            width: config.width,
+            height: config.height,
+        })
+    }
+
+    /// Reads the color target back and writes it to `path` as an RGBA PNG.
+    /// Blocks until everything submitted so far has finished.
+    pub fn save_png(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let row_bytes = self.width * 4;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("screenshot readback"),
+            size: padded_row_bytes as u64 * self.height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("screenshot copy encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        let swap = swaps_red_blue(self.format)?;
+        let mut pixels = Vec::with_capacity((row_bytes * self.height) as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for row in mapped.chunks_exact(padded_row_bytes as usize) {
+                pixels.extend_from_slice(&row[..row_bytes as usize]);
+            }
+        }
+        readback.unmap();
+        if swap {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(())
+    }
+}
+
+/// Whether `format` stores blue first, or an error if it is not a format a
+/// PNG can be written from.
+fn swaps_red_blue(format: wgpu::TextureFormat) -> Result<bool, Box<dyn Error>> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+        other => Err(format!("cannot save screenshots from {other:?} targets").into()),
+    }
+}
@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::terrain::{self, Grid};
+
+/// Where the overlay saves and loads a session unless given another path.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const DEFAULT_PATH: &str = "session.txt";
+
+/// Edits worth keeping between runs: a terrain's paint layer and the overlay
+/// scale. Saved as `key = value` lines, like a world file, with the paint in a
+/// PNG beside it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub paint: Option<PaintLayer>,
+    /// Overlay scale multiplier; `None` keeps the current one.
+    pub ui_scale: Option<f32>,
+}
+
+/// A terrain's hand-painted colors, one RGBA texel per grid vertex in the
+/// grid's row-major order. Saved as 8 bits per channel, as they are drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaintLayer {
+    pub grid: Grid,
+    pub texels: Vec<[f32; 4]>,
+}
+
+impl Session {
+    /// Writes the session to `path`, and its paint to `paint_path(path)`.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut text = String::from("# terrain editor session\n");
+        if let Some(paint) = &self.paint {
+            let image = paint_path(path);
+            write_paint(&image, paint)
+                .map_err(|err| format!("writing {}: {err}", image.display()))?;
+            let name = image.file_name().unwrap_or_default().to_string_lossy();
+            text.push_str(&format!("paint = {name}\n"));
+        }
+        if let Some(scale) = self.ui_scale {
+            text.push_str(&format!("ui_scale = {scale}\n"));
+        }
+        std::fs::write(path, text).map_err(|err| format!("writing {}: {err}", path.display()))?;
+        Ok(())
+    }
+
+    /// Reads a session file; see `parse`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("reading {}: {err}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, dir).map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+
+    /// Parses `key = value` lines the way `WorldConfig::parse` does. `paint`
+    /// names the paint PNG, relative to `dir`; `ui_scale` must be positive.
+    fn parse(text: &str, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut session = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+            match key.trim() {
+                "paint" => session.paint = Some(read_paint(&dir.join(value.trim()))?),
+                "ui_scale" => {
+                    let scale = value.trim().parse::<f32>()?;
+                    if !(scale.is_finite() && scale > 0.0) {
+                        let line = number + 1;
+                        return Err(format!("line {line}: ui_scale must be positive").into());
+                    }
+                    session.ui_scale = Some(scale);
+                }
+                key => return Err(format!("line {}: unknown key `{key}`", number + 1).into()),
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// `session.txt` keeps its paint in `session-paint.png`.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+fn paint_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}-paint.png"))
+}
+
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+fn write_paint(path: &Path, paint: &PaintLayer) -> Result<(), Box<dyn Error>> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, paint.grid.lon, paint.grid.lat);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let bytes = terrain::paint_bytes(&paint.texels);
+    encoder
+        .write_header()?
+        .write_image_data(bytemuck::cast_slice(&bytes))?;
+    Ok(())
+}
+
+fn read_paint(path: &Path) -> Result<PaintLayer, Box<dyn Error>> {
+    let file = File::open(path).map_err(|err| format!("reading {}: {err}", path.display()))?;
+    let mut reader = png::Decoder::new(BufReader::new(file)).read_info()?;
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut bytes)?;
+    if (frame.color_type, frame.bit_depth) != (png::ColorType::Rgba, png::BitDepth::Eight) {
+        return Err(format!("{}: paint must be an 8-bit RGBA PNG", path.display()).into());
+    }
+    let texels = bytes[..frame.buffer_size()]
+        .chunks_exact(4)
+        .map(|texel| [0, 1, 2, 3].map(|c| texel[c] as f32 / 255.0))
+        .collect();
+    let grid = Grid {
+        lon: frame.width,
+        lat: frame.height,
+    };
+    Ok(PaintLayer { grid, texels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_round_trips_through_a_saved_session() {
+        let grid = Grid::new(4);
+        let texels = (0..grid.points())
+            .map(|i| [i, i * 3, 255 - i, i * 7 % 256].map(|c| c as f32 / 255.0))
+            .collect();
+        let session = Session {
+            paint: Some(PaintLayer { grid, texels }),
+            ui_scale: Some(1.35),
+        };
+        let dir = std::env::temp_dir().join(format!("session-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("saved.txt");
+        session.save(&path).unwrap();
+        assert!(dir.join("saved-paint.png").exists());
+        let loaded = Session::load(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap(), session);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        let dir = Path::new("");
+        let empty = Session::parse("# nothing saved\n", dir).unwrap();
+        assert_eq!(empty, Session::default());
+        let err = Session::parse("ui = 1", dir).unwrap_err().to_string();
+        assert_eq!(err, "line 1: unknown key `ui`");
+        let err = Session::parse("paint", dir).unwrap_err().to_string();
+        assert_eq!(err, "line 1: expected key = value");
+        let err = Session::parse("\nui_scale = 0", dir).unwrap_err();
+        let err = err.to_string();
+        assert_eq!(err, "line 2: ui_scale must be positive");
+    }
+}
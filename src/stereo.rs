@@ -0,0 +1,234 @@
+use glam::Vec3;
+
+#[cfg(feature = "hot-reload")]
+use crate::hot_reload;
+
+pub const DEFAULT_EYE_SEPARATION: f32 = 2.5;
+
+#[derive(Copy, Clone, Debug)]
+pub struct StereoSettings {
+    pub enabled: bool,
+    pub eye_separation: f32,
+}
+
+impl Default for StereoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            eye_separation: DEFAULT_EYE_SEPARATION,
+        }
+    }
+}
+
+impl StereoSettings {
+    /// Left and right eye positions, offset along the camera's right axis.
+    pub fn eyes(&self, eye: Vec3, target: Vec3, up: Vec3) -> [Vec3; 2] {
+        let right = (target - eye).cross(up).normalize_or_zero();
+        let offset = right * (self.eye_separation * 0.5);
+        [eye - offset, eye + offset]
+    }
+}
+
+struct EyeTargets {
+    views: [wgpu::TextureView; 2],
+    bind_group: wgpu::BindGroup,
+}
+
+/// Offscreen per-eye color targets and the red/cyan composite pass.
+pub struct Anaglyph {
+    pipeline: wgpu::RenderPipeline,
+    #[cfg(feature = "hot-reload")]
+    pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(feature = "hot-reload")]
+    format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    targets: EyeTargets,
+}
+
+impl Anaglyph {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("anaglyph bind group layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("anaglyph sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("anaglyph pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = create_pipeline(
+            device,
+            &pipeline_layout,
+            config.format,
+            include_str!("../shaders/anaglyph.wgsl"),
+        );
+
+        let targets = create_targets(device, config, &bind_group_layout, &sampler);
+
+        Self {
+            pipeline,
+            #[cfg(feature = "hot-reload")]
+            pipeline_layout,
+            #[cfg(feature = "hot-reload")]
+            format: config.format,
+            bind_group_layout,
+            sampler,
+            targets,
+        }
+    }
+
+    /// Rebuilds the composite pipeline from new WGSL, keeping the current one
+    /// if it fails to compile or validate.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_shader(&mut self, device: &wgpu::Device, source: &str) -> Result<(), String> {
+        self.pipeline = hot_reload::validated(device, || {
+            create_pipeline(device, &self.pipeline_layout, self.format, source)
+        })?;
+        Ok(())
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.targets = create_targets(device, config, &self.bind_group_layout, &self.sampler);
+    }
+
+    pub fn eye_view(&self, eye: usize) -> &wgpu::TextureView {
+        &self.targets.views[eye]
+    }
+
+    pub fn composite(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("anaglyph composite pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.targets.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// Compiles the composite shader and builds its fullscreen pipeline.
+fn create_pipeline(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("anaglyph shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("anaglyph pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+) -> EyeTargets {
+    let make_view = |label| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: config.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    let views = [make_view("left eye target"), make_view("right eye target")];
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("anaglyph bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&views[0]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&views[1]),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+
+    EyeTargets { views, bind_group }
+}
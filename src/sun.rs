@@ -0,0 +1,112 @@
+use glam::Vec3;
+
+const DEFAULT_AZIMUTH_DEG: f32 = 26.6;
+const DEFAULT_ELEVATION_DEG: f32 = 63.6;
+const DEFAULT_TOON_BANDS: u32 = 4;
+const DEFAULT_SKY_AMBIENT: [f32; 3] = [0.85, 0.92, 1.0];
+const DEFAULT_GROUND_AMBIENT: [f32; 3] = [0.45, 0.4, 0.34];
+const DEFAULT_AMBIENT_INTENSITY: f32 = 0.5;
+/// How fast a held nudge key turns the sun, in degrees per second.
+const NUDGE_DEGREES_PER_SECOND: f32 = 45.0;
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const MIN_TOON_BANDS: u32 = 2;
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const MAX_TOON_BANDS: u32 = 8;
+
+/// How the terrain turns the angle to the sun into diffuse light.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightingModel {
+    /// Plain `n . l`, dark past the terminator.
+    Lambert,
+    /// `(n . l * 0.5 + 0.5)^2`: light wraps around the terminator, softening
+    /// relief on the night side.
+    HalfLambert,
+    /// Lambert quantized into a fixed number of flat bands.
+    Toon,
+}
+
+impl LightingModel {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub const ALL: [LightingModel; 3] = [
+        LightingModel::Lambert,
+        LightingModel::HalfLambert,
+        LightingModel::Toon,
+    ];
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn label(self) -> &'static str {
+        match self {
+            LightingModel::Lambert => "Lambert",
+            LightingModel::HalfLambert => "Half-Lambert",
+            LightingModel::Toon => "Toon",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SunSettings {
+    pub azimuth: f32,
+    pub elevation: f32,
+    pub model: LightingModel,
+    /// Light levels in `Toon` mode, including unlit.
+    pub toon_bands: u32,
+    /// Ambient light reaching surfaces that face straight up.
+    pub sky_ambient: [f32; 3],
+    /// Ambient light reaching surfaces that face straight down.
+    pub ground_ambient: [f32; 3],
+    /// Scale applied to both ambient colors.
+    pub ambient_intensity: f32,
+}
+
+impl Default for SunSettings {
+    fn default() -> Self {
+        Self {
+            azimuth: DEFAULT_AZIMUTH_DEG,
+            elevation: DEFAULT_ELEVATION_DEG,
+            model: LightingModel::Lambert,
+            toon_bands: DEFAULT_TOON_BANDS,
+            sky_ambient: DEFAULT_SKY_AMBIENT,
+            ground_ambient: DEFAULT_GROUND_AMBIENT,
+            ambient_intensity: DEFAULT_AMBIENT_INTENSITY,
+        }
+    }
+}
+
+impl SunSettings {
+    /// Unit vector pointing towards the sun in world space.
+    pub fn direction(&self) -> Vec3 {
+        let az = self.azimuth.to_radians();
+        let el = self.elevation.to_radians();
+        Vec3::new(el.cos() * az.cos(), el.sin(), el.cos() * az.sin())
+    }
+
+    /// Turns the sun for `dt` seconds of held nudge keys, `azimuth` and
+    /// `elevation` each -1, 0 or 1. Azimuth wraps around; elevation stops at
+    /// straight up or down.
+    pub fn nudge(&mut self, azimuth: f32, elevation: f32, dt: f32) {
+        let step = NUDGE_DEGREES_PER_SECOND * dt;
+        self.azimuth = (self.azimuth + azimuth * step).rem_euclid(360.0);
+        self.elevation = (self.elevation + elevation * step).clamp(-90.0, 90.0);
+    }
+
+    /// Shader parameters: x selects the model in `LightingModel::ALL` order,
+    /// y is the toon band count.
+    pub fn lighting_uniform(&self) -> [f32; 4] {
+        let model = match self.model {
+            LightingModel::Lambert => 0.0,
+            LightingModel::HalfLambert => 1.0,
+            LightingModel::Toon => 2.0,
+        };
+        [model, self.toon_bands.max(MIN_TOON_BANDS) as f32, 0.0, 0.0]
+    }
+
+    /// Hemisphere ambient colors, sky then ground, with the intensity
+    /// already applied.
+    pub fn ambient_uniform(&self) -> [[f32; 4]; 2] {
+        let scale = |c: [f32; 3]| {
+            let [r, g, b] = c.map(|v| v * self.ambient_intensity);
+            [r, g, b, 0.0]
+        };
+        [scale(self.sky_ambient), scale(self.ground_ambient)]
+    }
+}
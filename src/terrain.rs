@@ -1,28 +1,95 @@
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec2, Vec3};
 use rand::Rng;
+use std::cell::{Cell, RefCell};
+use std::io::Write;
 use std::num::NonZeroU64;
+use std::ops::{Range, RangeInclusive};
 use wgpu::util::DeviceExt;
 
-pub const GRID: u32 = 256;
-pub const WORLD_RADIUS: f32 = 100.0;
-pub const HEIGHT_AMPLITUDE: f32 = 10.0;
-pub const WATER_LEVEL: f32 = 0.0;
-const LON_POINTS: u32 = GRID + 1;
-const LAT_POINTS: u32 = GRID;
-const MAP_WIDTH: f32 = WORLD_RADIUS * std::f32::consts::TAU;
-const MAP_HEIGHT: f32 = WORLD_RADIUS * std::f32::consts::PI;
+use crate::biome::{self, Biome};
+use crate::brush::{self, BrushMode, BrushSettings};
+use crate::depth::{self, DepthMode};
+use crate::detail::{DetailMap, DetailSettings};
+#[cfg(feature = "hot-reload")]
+use crate::hot_reload;
+use crate::id_buffer;
+use crate::jobs::{BackgroundJob, JobStatus, Progress};
+use crate::mesh::{self, DrawStats, IndexBuffer};
+use crate::picking::{self, Ray};
+use crate::view::SceneView;
+use crate::world::WorldConfig;
+
 const CONTINENT_FREQ: f32 = 1.9;
 const HILL_FREQ: f32 = 8.2;
 const MOUNTAIN_FREQ: f32 = 10.2;
 const DETAIL_FREQ: f32 = 19.0;
 const WARP_FREQ: f32 = 0.75;
 const MOISTURE_FREQ: f32 = 0.8;
+const TILE_DRIFT: f32 = 2.0;
+const PICK_ITERATIONS: usize = 4;
+/// Object id the base terrain writes into the id buffer. Tiles take the
+/// next free ids above it.
+pub const TERRAIN_ID: u32 = 1;
+pub const DEFAULT_SMOOTH_PASSES: u32 = 2;
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const MAX_SMOOTH_PASSES: u32 = 16;
 const DEFAULT_SEA_THRESHOLD: f32 = 0.190;
 const DEFAULT_BEACH_MAX_HEIGHT: f32 = 0.09;
 const DEFAULT_DESERT_MOISTURE_MAX: f32 = 0.25;
 const DEFAULT_SEMI_ARID_MOISTURE_MAX: f32 = 0.35;
 const DEFAULT_LAND_ELEVATION_BIAS: f32 = 0.06;
 const DEFAULT_TEMPERATURE_BIAS: f32 = 0.110;
+const DEFAULT_SNOW_TEMPERATURE_MAX: f32 = 0.15;
+const DEFAULT_TUNDRA_TEMPERATURE_MAX: f32 = 0.3;
+const DEFAULT_TAIGA_TEMPERATURE_MAX: f32 = 0.42;
+const DEFAULT_TROPICAL_TEMPERATURE_MIN: f32 = 0.72;
+/// Temperature lost per unit of normalized land height.
+const LAPSE_RATE: f32 = 0.45;
+const DEFAULT_WARP_STRENGTH: f32 = 0.0;
+const DEFAULT_WARP_SCALE: f32 = 2.5;
+const DEFAULT_FEATURE_SIZE: f32 = 1.0;
+const DEFAULT_BIOME_BLEND: f32 = 0.02;
+pub const MAX_BIOME_BLEND: f32 = 0.15;
+const DEFAULT_BASE_COLOR: [f32; 3] = [0.08, 0.43, 0.22];
+const DEFAULT_SEABED_COLOR: [f32; 3] = [0.1, 0.17, 0.24];
+const DEFAULT_SEABED_TRANSITION: f32 = 1.5;
+const DEFAULT_SNOW_SPECULAR: f32 = 0.3;
+const DEFAULT_ROCK_SPECULAR: f32 = 0.15;
+const DEFAULT_SAND_SPECULAR: f32 = 0.0;
+const DEFAULT_VEGETATION_SPECULAR: f32 = 0.0;
+
+/// Vertex counts of the terrain grid, stored row-major from the north pole.
+/// Each latitude row repeats its first vertex at the end, on the seam
+/// meridian, so rows are one vertex longer than there are rows.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub lon: u32,
+    pub lat: u32,
+}
+
+impl Grid {
+    pub const fn new(rows: u32) -> Self {
+        Self {
+            lon: rows + 1,
+            lat: rows,
+        }
+    }
+
+    pub fn points(self) -> usize {
+        (self.lon * self.lat) as usize
+    }
+
+    pub fn index(self, x: u32, z: u32) -> usize {
+        (z * self.lon + x) as usize
+    }
+
+    fn uv(self, x: u32, z: u32) -> Vec2 {
+        Vec2::new(
+            x as f32 / (self.lon - 1) as f32,
+            z as f32 / (self.lat - 1) as f32,
+        )
+    }
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct TerrainSettings {
@@ -32,6 +99,39 @@ pub struct TerrainSettings {
     pub semi_arid_moisture_max: f32,
     pub land_elevation_bias: f32,
     pub temperature_bias: f32,
+    /// Below this temperature land is permanent snow and ice.
+    pub snow_temperature_max: f32,
+    pub tundra_temperature_max: f32,
+    /// Moist land below this temperature grows boreal forest.
+    pub taiga_temperature_max: f32,
+    /// Above this temperature grassland turns to savanna and forest to jungle.
+    pub tropical_temperature_min: f32,
+    /// Half-width of the blend across each biome threshold, in the units of
+    /// the threshold (normalized height, temperature or moisture); 0 gives
+    /// hard edges.
+    pub biome_blend: f32,
+    /// How far the extra domain warp displaces noise lookups; 0 disables it.
+    pub warp_strength: f32,
+    /// Frequency of the extra warp field; higher values give finer wiggles.
+    pub warp_scale: f32,
+    /// Scales every noise layer, warps included: above 1 gives fewer, larger
+    /// continents, below 1 many small islands.
+    pub feature_size: f32,
+    /// Land color wherever no biome override applies (`Biome::Forest`):
+    /// moist, temperate lowland by default.
+    pub base_color: [f32; 3],
+    /// Tone submerged land shifts toward under the water surface.
+    pub seabed_color: [f32; 3],
+    /// Depth below the water level, in world units, over which land fades
+    /// fully to `seabed_color`.
+    pub seabed_transition: f32,
+    /// Strength of the sun's glint on snow and ice.
+    pub snow_specular: f32,
+    pub rock_specular: f32,
+    /// Beaches and deserts.
+    pub sand_specular: f32,
+    /// Tundra, grassland, savanna and every kind of forest.
+    pub vegetation_specular: f32,
 }
 
 impl Default for TerrainSettings {
@@ -43,10 +143,65 @@ impl Default for TerrainSettings {
             semi_arid_moisture_max: DEFAULT_SEMI_ARID_MOISTURE_MAX,
             land_elevation_bias: DEFAULT_LAND_ELEVATION_BIAS,
             temperature_bias: DEFAULT_TEMPERATURE_BIAS,
+            snow_temperature_max: DEFAULT_SNOW_TEMPERATURE_MAX,
+            tundra_temperature_max: DEFAULT_TUNDRA_TEMPERATURE_MAX,
+            taiga_temperature_max: DEFAULT_TAIGA_TEMPERATURE_MAX,
+            tropical_temperature_min: DEFAULT_TROPICAL_TEMPERATURE_MIN,
+            biome_blend: DEFAULT_BIOME_BLEND,
+            warp_strength: DEFAULT_WARP_STRENGTH,
+            warp_scale: DEFAULT_WARP_SCALE,
+            feature_size: DEFAULT_FEATURE_SIZE,
+            base_color: DEFAULT_BASE_COLOR,
+            seabed_color: DEFAULT_SEABED_COLOR,
+            seabed_transition: DEFAULT_SEABED_TRANSITION,
+            snow_specular: DEFAULT_SNOW_SPECULAR,
+            rock_specular: DEFAULT_ROCK_SPECULAR,
+            sand_specular: DEFAULT_SAND_SPECULAR,
+            vegetation_specular: DEFAULT_VEGETATION_SPECULAR,
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Whether the two settings produce different heights (as opposed to
+    /// only different coloring).
+    fn shape_differs(&self, other: &Self) -> bool {
+        self.sea_threshold != other.sea_threshold
+            || self.land_elevation_bias != other.land_elevation_bias
+            || self.warp_strength != other.warp_strength
+            || self.warp_scale != other.warp_scale
+            || self.feature_size != other.feature_size
+    }
+}
+
+/// Where a terrain sits in the scene: scaled about the origin, then moved by
+/// `offset`. Applies to the globe and the flat map alike.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TerrainTransform {
+    pub offset: Vec3,
+    /// Uniform, so normals and the globe's roundness survive it.
+    pub scale: f32,
+}
+
+impl Default for TerrainTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vec3::ZERO,
+            scale: 1.0,
         }
     }
 }
 
+impl TerrainTransform {
+    fn to_world(self, local: Vec3) -> Vec3 {
+        local * self.scale + self.offset
+    }
+
+    fn to_local(self, world: Vec3) -> Vec3 {
+        (world - self.offset) / self.scale
+    }
+}
+
 #[derive(Copy, Clone)]
 struct NoiseSeeds {
     continent: u32,
@@ -70,23 +225,41 @@ impl NoiseSeeds {
     }
 }
 
+/// Terrain vertex, 44 bytes. Color is derived entirely in the fragment shader
+/// from height, moisture, temperature and the shading normal.
+///
+/// | location | field         | format    | contents                                |
+/// |----------|---------------|-----------|-----------------------------------------|
+/// | 0        | `pos`         | Float32x3 | globe position, radius + height         |
+/// | 1        | `normal`      | Float32x3 | globe normal                            |
+/// | 2        | `flat_pos`    | Float32x3 | map `u`, map `v`, height                |
+/// | 3        | `moisture`    | Float32   | biome moisture in `[0, 1]`              |
+/// | 5        | `temperature` | Float32   | `1 - |lat| - altitude * LAPSE_RATE`     |
+///
+/// The hand-painted color layer lives in a second vertex buffer (slot 1,
+/// location 4, `Unorm8x4`) so brush strokes only re-upload four bytes per
+/// touched vertex.
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
     pos: [f32; 3],
     normal: [f32; 3],
-    color: [f32; 3],
     flat_pos: [f32; 3],
+    moisture: f32,
+    temperature: f32,
 }
 
 impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
         0 => Float32x3,
         1 => Float32x3,
         2 => Float32x3,
-        3 => Float32x3
+        3 => Float32,
+        5 => Float32
     ];
 
+    const PAINT_ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![4 => Unorm8x4];
+
     fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as u64,
@@ -94,6 +267,14 @@ impl Vertex {
             attributes: &Self::ATTRIBS,
         }
     }
+
+    fn paint_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[u8; 4]>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::PAINT_ATTRIBS,
+        }
+    }
 }
 
 #[repr(C)]
@@ -101,28 +282,233 @@ impl Vertex {
 struct Globals {
     view_proj: [[f32; 4]; 4],
     morph: [f32; 4],
+    transform: [f32; 4],
+    sun_dir: [f32; 4],
+    camera_pos: [f32; 4],
+    lighting: [f32; 4],
+    sky_ambient: [f32; 4],
+    ground_ambient: [f32; 4],
+    depth: [f32; 4],
+    biome: [f32; 4],
+    climate: [f32; 4],
+    shape: [f32; 4],
+    detail: [f32; 4],
+    base: [f32; 4],
+    seabed: [f32; 4],
+    specular: [f32; 4],
+    object: [u32; 4],
+}
+
+/// Pipelines and the detail map every terrain in the scene draws with, built
+/// once however many terrains there are.
+pub struct TerrainRenderer {
+    pipelines: [wgpu::RenderPipeline; 2],
+    id_pipelines: [wgpu::RenderPipeline; 2],
+    bind_group_layout: wgpu::BindGroupLayout,
+    #[cfg(feature = "hot-reload")]
+    pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(feature = "hot-reload")]
+    format: wgpu::TextureFormat,
+    detail_map: DetailMap,
+}
+
+impl TerrainRenderer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("terrain bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(std::mem::size_of::<Globals>() as u64),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("terrain pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let (pipelines, id_pipelines) = create_pipelines(
+            device,
+            &pipeline_layout,
+            format,
+            include_str!("../shaders/terrain.wgsl"),
+        );
+
+        Self {
+            pipelines,
+            id_pipelines,
+            bind_group_layout,
+            #[cfg(feature = "hot-reload")]
+            pipeline_layout,
+            #[cfg(feature = "hot-reload")]
+            format,
+            detail_map: DetailMap::new(device, queue),
+        }
+    }
+
+    /// Rebuilds the pipelines from new WGSL, keeping the current ones if it
+    /// fails to compile or validate.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_shader(&mut self, device: &wgpu::Device, source: &str) -> Result<(), String> {
+        let (pipelines, id_pipelines) = hot_reload::validated(device, || {
+            create_pipelines(device, &self.pipeline_layout, self.format, source)
+        })?;
+        self.pipelines = pipelines;
+        self.id_pipelines = id_pipelines;
+        Ok(())
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        terrains: &'a [Terrain],
+        depth_mode: DepthMode,
+    ) {
+        pass.set_pipeline(&self.pipelines[depth_mode.is_reversed() as usize]);
+        for terrain in terrains {
+            terrain.draw(pass);
+        }
+    }
+
+    /// Draws the terrains' object id into an id-buffer pass.
+    pub fn draw_id<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        terrains: &'a [Terrain],
+        depth_mode: DepthMode,
+    ) {
+        pass.set_pipeline(&self.id_pipelines[depth_mode.is_reversed() as usize]);
+        for terrain in terrains {
+            terrain.draw(pass);
+        }
+    }
 }
 
+/// One heightfield in the scene, with its own buffers and bind group; the
+/// pipelines come from the shared `TerrainRenderer`.
 pub struct Terrain {
-    pipeline: wgpu::RenderPipeline,
+    /// Object id written into the id buffer, unique among the scene's
+    /// terrains.
+    id: u32,
     vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    index_count: u32,
+    /// Second vertex buffer when double-buffered. Whole-grid uploads fill it
+    /// and swap it with `vertex_buffer`, so the new vertices never land in
+    /// the buffer a frame still in flight is drawing from.
+    spare_vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: IndexBuffer,
     uniform: wgpu::Buffer,
+    /// Contents of the last `uniform` write, so an unchanged view is not
+    /// uploaded again.
+    uploaded: Cell<Option<Globals>>,
+    /// `uniform` writes so far, shown beside the draw stats.
+    uniform_uploads: Cell<u32>,
+    /// Index ranges left after horizon culling the last view; `None` draws
+    /// everything.
+    visible: RefCell<Option<Vec<Range<u32>>>>,
+    stats: Cell<DrawStats>,
     bind_group: wgpu::BindGroup,
+    world: WorldConfig,
+    transform: TerrainTransform,
+    /// Height of the sea surface; `Water` is rebuilt to follow it.
+    water_level: f32,
     settings: TerrainSettings,
+    detail: DetailSettings,
     seeds: NoiseSeeds,
+    origin: Vec2,
+    heights: Vec<f32>,
+    /// `height_span` of each latitude row of `heights`, so an upload of a
+    /// few rows rescans only those.
+    row_spans: Vec<(f32, f32)>,
+    /// Lowest and highest entry of `heights`, bounding the globe's relief.
+    height_span: (f32, f32),
+    moisture: Vec<f32>,
+    revision: u64,
+    paint: Vec<[f32; 4]>,
+    paint_buffer: wgpu::Buffer,
+    /// Noise being resampled off the main thread; see `regenerate`.
+    generation: Option<BackgroundJob<Generated>>,
+}
+
+/// Output of a background `regenerate`, ready to upload.
+struct Generated {
+    heights: Vec<f32>,
+    moisture: Vec<f32>,
+    vertices: Vec<Vertex>,
 }
 
 impl Terrain {
     pub fn new(
         device: &wgpu::Device,
-        format: wgpu::TextureFormat,
+        renderer: &TerrainRenderer,
+        world: &WorldConfig,
         rng: &mut impl Rng,
     ) -> Self {
         let settings = TerrainSettings::default();
         let seeds = NoiseSeeds::new(rng);
-        let (vertices, indices) = generate_mesh(seeds, &settings);
+        let origin = Vec2::ZERO;
+        Self::with_noise(device, renderer, world, seeds, settings, origin, TERRAIN_ID)
+    }
+
+    /// A terrain continuing this one's world at `origin`: the same seeds,
+    /// shape and water level, so tiles whose origins differ by a whole unit
+    /// east or west line up along their shared border (see `tile_drift`).
+    /// `world` may pick another grid, and
+    /// `id` is the tile's object id.
+    pub fn tile(
+        &self,
+        device: &wgpu::Device,
+        renderer: &TerrainRenderer,
+        world: &WorldConfig,
+        origin: Vec2,
+        id: u32,
+    ) -> Self {
+        let (seeds, settings) = (self.seeds, self.settings);
+        let mut tile = Self::with_noise(device, renderer, world, seeds, settings, origin, id);
+        tile.water_level = self.water_level;
+        tile.detail = self.detail;
+        tile
+    }
+
+    fn with_noise(
+        device: &wgpu::Device,
+        renderer: &TerrainRenderer,
+        world: &WorldConfig,
+        seeds: NoiseSeeds,
+        settings: TerrainSettings,
+        origin: Vec2,
+        id: u32,
+    ) -> Self {
+        let progress = Progress::new(world.grid.lat);
+        let (heights, moisture) = generate_fields(seeds, &settings, origin, world, &progress);
+        let vertices = build_vertices(&heights, &moisture, world);
+        let indices = generate_indices(world.grid);
+        debug_assert_reference_winding(&indices, world);
+        let paint = vec![[0.0; 4]; vertices.len()];
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("terrain vertices"),
@@ -130,202 +516,847 @@ impl Terrain {
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
 
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("terrain indices"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
+        let paint_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("terrain paint"),
+            contents: bytemuck::cast_slice(&paint_bytes(&paint)),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
 
+        let index_buffer = IndexBuffer::new(device, "terrain indices", &indices, vertices.len());
+
         let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("terrain globals"),
             contents: bytemuck::bytes_of(&Globals {
                 view_proj: Mat4::IDENTITY.to_cols_array_2d(),
                 morph: [0.0; 4],
+                transform: [0.0, 0.0, 0.0, 1.0],
+                sun_dir: [0.0, 1.0, 0.0, 0.0],
+                camera_pos: [0.0; 4],
+                lighting: [0.0; 4],
+                sky_ambient: [0.0; 4],
+                ground_ambient: [0.0; 4],
+                depth: [0.0; 4],
+                biome: [0.0; 4],
+                climate: [0.0; 4],
+                shape: [world.height_amplitude, world.water_level, 0.0, 0.0],
+                detail: [0.0; 4],
+                base: [0.0; 4],
+                seabed: [0.0; 4],
+                specular: [0.0; 4],
+                object: [id, 0, 0, 0],
             }),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("terrain bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(std::mem::size_of::<Globals>() as u64),
-                },
-                count: None,
-            }],
-        });
-
+        let detail_map = &renderer.detail_map;
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("terrain bind group"),
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform.as_entire_binding(),
-            }],
-        });
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("terrain shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/terrain.wgsl").into()),
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("terrain pipeline layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("terrain pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::layout()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                cull_mode: Some(wgpu::Face::Back),
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
+            layout: &renderer.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&detail_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&detail_map.sampler),
+                },
+            ],
         });
 
         Self {
-            pipeline,
+            id,
             vertex_buffer,
+            spare_vertex_buffer: None,
             index_buffer,
-            index_count: indices.len() as u32,
             uniform,
+            uploaded: Cell::new(None),
+            uniform_uploads: Cell::new(0),
+            visible: RefCell::new(None),
+            stats: Cell::new(DrawStats::new(None, indices.len() as u32)),
             bind_group,
+            world: *world,
+            transform: TerrainTransform::default(),
+            water_level: world.water_level,
             settings,
+            detail: DetailSettings::default(),
             seeds,
+            origin,
+            row_spans: row_spans(&heights, world.grid),
+            height_span: height_span(&heights),
+            heights,
+            moisture,
+            revision: 0,
+            paint,
+            paint_buffer,
+            generation: None,
         }
     }
 
-    pub fn update_view(
-        &self,
-        queue: &wgpu::Queue,
-        view_proj: Mat4,
-        morph: f32,
-        rotation: f32,
-    ) {
+    pub fn update_view(&self, queue: &wgpu::Queue, view: &SceneView) {
+        self.cull(view);
+        let map = map_size(self.world.radius);
         let globals = Globals {
-            view_proj: view_proj.to_cols_array_2d(),
-            morph: [
-                morph.clamp(0.0, 1.0),
-                rotation,
-                MAP_WIDTH,
-                MAP_HEIGHT,
+            view_proj: view.view_proj.to_cols_array_2d(),
+            morph: [view.morph.clamp(0.0, 1.0), view.rotation, map.x, map.y],
+            transform: self.transform.offset.extend(self.transform.scale).into(),
+            sun_dir: view.sun_dir.extend(0.0).into(),
+            camera_pos: view.eye.extend(1.0).into(),
+            lighting: view.lighting,
+            sky_ambient: view.ambient[0],
+            ground_ambient: view.ambient[1],
+            depth: view.depth_mode.uniform(view.far),
+            biome: [
+                self.settings.beach_max_height,
+                self.settings.desert_moisture_max,
+                self.settings.semi_arid_moisture_max,
+                self.settings.temperature_bias,
+            ],
+            climate: [
+                self.settings.snow_temperature_max,
+                self.settings.tundra_temperature_max,
+                self.settings.taiga_temperature_max,
+                self.settings.tropical_temperature_min,
+            ],
+            shape: [
+                self.world.height_amplitude,
+                self.water_level,
+                biome::blend_width(self.settings.biome_blend),
+                0.0,
+            ],
+            detail: [
+                self.detail.strength,
+                self.detail.tiling,
+                self.detail.mip_bias,
+                self.detail.fade_distance,
+            ],
+            base: [
+                self.settings.base_color[0],
+                self.settings.base_color[1],
+                self.settings.base_color[2],
+                0.0,
+            ],
+            seabed: [
+                self.settings.seabed_color[0],
+                self.settings.seabed_color[1],
+                self.settings.seabed_color[2],
+                self.settings.seabed_transition,
+            ],
+            specular: [
+                self.settings.snow_specular,
+                self.settings.rock_specular,
+                self.settings.sand_specular,
+                self.settings.vegetation_specular,
             ],
+            object: [self.id, 0, 0, 0],
         };
-        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&globals));
+        let unchanged = self
+            .uploaded
+            .get()
+            .is_some_and(|last| bytemuck::bytes_of(&last) == bytemuck::bytes_of(&globals));
+        if !unchanged {
+            queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&globals));
+            self.uploaded.set(Some(globals));
+            self.uniform_uploads.set(self.uniform_uploads.get() + 1);
+        }
+    }
+
+    /// Keeps the quads `view.eye` may see when horizon culling is on. All the
+    /// relief stands on a solid core as high as the lowest point, so quads
+    /// past the core's horizon, plus however far the highest peak can still
+    /// show over it, are hidden.
+    fn cull(&self, view: &SceneView) {
+        let (low, high) = self.height_span;
+        let (inner, outer) = (self.world.radius + low, self.world.radius + high);
+        let eye = self.transform.to_local(view.eye);
+        let distance = eye.length();
+        let cull = view.horizon_cull && view.morph <= 0.0 && inner > 0.0 && distance > outer;
+        let visible = if cull {
+            let reach = (inner / distance).acos() + (inner / outer).acos();
+            let grid = self.world.grid;
+            mesh::horizon_ranges(grid.lat - 1, grid.lon - 1, eye, reach)
+        } else {
+            None
+        };
+        let count = self.index_buffer.count();
+        self.stats.set(DrawStats::new(visible.as_deref(), count));
+        *self.visible.borrow_mut() = visible;
+    }
+
+    /// What the last `draw` submitted, after any horizon culling.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn draw_stats(&self) -> DrawStats {
+        self.stats.get()
+    }
+
+    /// View uniform writes so far; `update_view` skips an unchanged one.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn uniform_uploads(&self) -> u32 {
+        self.uniform_uploads.get()
+    }
+
+    /// Object id this terrain writes into the id buffer.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Globe radius in world units; the flat map is `map_size(radius)`.
+    pub fn radius(&self) -> f32 {
+        self.world.radius
     }
 
+    /// Furthest the surface gets from the scene origin, on the globe or the
+    /// flat map, once placed by `transform`.
+    pub fn reach(&self) -> f32 {
+        let (min, max) = self.local_map_bounds();
+        let flat = min.abs().max(max.abs()).length();
+        let globe = self.world.radius + max.y.max(0.0);
+        self.transform.offset.length() + self.transform.scale * flat.max(globe)
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn transform(&self) -> TerrainTransform {
+        self.transform
+    }
+
+    /// Takes effect on the next `update_view`. A non-positive scale is
+    /// ignored, since it would turn the terrain inside out.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn set_transform(&mut self, transform: TerrainTransform) {
+        if transform.scale > 0.0 {
+            self.transform = transform;
+        }
+    }
+
+    /// World-space corners of a box around the surface at `morph`: the
+    /// globe's cube blended with the flat map's box, then placed by
+    /// `transform`. Every blend of a globe point and its map point lies inside.
+    pub fn bounds(&self, morph: f32) -> (Vec3, Vec3) {
+        let (min, max) = self.local_map_bounds();
+        let globe = Vec3::splat(self.world.radius + max.y.max(0.0));
+        let t = morph.clamp(0.0, 1.0);
+        (
+            self.transform.to_world((-globe).lerp(min, t)),
+            self.transform.to_world(globe.lerp(max, t)),
+        )
+    }
+
+    /// Corners of the box around the flat map's relief before `transform`:
+    /// the map's width and depth, and the lowest and highest terrain heights
+    /// after every edit so far. Cached by each upload, so this does no work.
+    fn local_map_bounds(&self) -> (Vec3, Vec3) {
+        let (low, high) = self.height_span;
+        let half = map_size(self.world.radius) * 0.5;
+        (
+            Vec3::new(-half.x, low, -half.y),
+            Vec3::new(half.x, high, half.y),
+        )
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
     pub fn settings(&self) -> TerrainSettings {
         self.settings
     }
 
-    pub fn set_settings(&mut self, queue: &wgpu::Queue, settings: TerrainSettings) {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn detail(&self) -> DetailSettings {
+        self.detail
+    }
+
+    /// Takes effect on the next `update_view`.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn set_detail(&mut self, detail: DetailSettings) {
+        self.detail = detail;
+    }
+
+    /// Biome thresholds are read by the shader on the next `update_view`; only
+    /// changes to the land shape rebuild the mesh.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn set_settings(&mut self, settings: TerrainSettings) {
+        let reshape = settings.shape_differs(&self.settings);
         self.settings = settings;
-        self.regenerate(queue);
+        if reshape {
+            self.regenerate();
+        }
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn double_buffered(&self) -> bool {
+        self.spare_vertex_buffer.is_some()
+    }
+
+    /// Allocates or frees the spare vertex buffer, doubling vertex memory
+    /// while on. The spare is filled by the next whole-grid upload before it
+    /// is ever drawn.
+    pub fn set_double_buffered(&mut self, device: &wgpu::Device, enabled: bool) {
+        if enabled == self.double_buffered() {
+            return;
+        }
+        self.spare_vertex_buffer = enabled.then(|| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("terrain vertices (spare)"),
+                size: self.vertex_buffer.size(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn origin(&self) -> Vec2 {
+        self.origin
     }
 
-    pub fn randomize(&mut self, queue: &wgpu::Queue, rng: &mut impl Rng) {
+    /// Moves the tile within the world-space noise domain. Terrains whose
+    /// origins differ by a whole unit east or west sample continuous noise and
+    /// line up along their shared border.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn set_origin(&mut self, origin: Vec2) {
+        self.origin = origin;
+        self.regenerate();
+    }
+
+    /// Reseeds the noise. With `jitter_water` the water level also moves to
+    /// a random height within the world's `water_level_jitter` of its
+    /// `water_level`; otherwise it stays where it is.
+    pub fn randomize(&mut self, rng: &mut impl Rng, jitter_water: bool) {
         self.seeds = NoiseSeeds::new(rng);
-        self.regenerate(queue);
+        if jitter_water {
+            let jitter = self.world.water_level_jitter;
+            self.water_level = self.world.water_level + rng.gen_range(-jitter..=jitter);
+        }
+        self.regenerate();
+    }
+
+    pub fn water_level(&self) -> f32 {
+        self.water_level
+    }
+
+    /// Resamples the noise for the current seeds, settings and origin on a
+    /// background thread, cancelling any resample still running. The old
+    /// heightfield stays on screen, and takes edits such as smoothing, until
+    /// `poll_generation` uploads the new one.
+    fn regenerate(&mut self) {
+        let (seeds, settings, origin, world) = (self.seeds, self.settings, self.origin, self.world);
+        self.generation = Some(BackgroundJob::spawn(
+            "terrain generation",
+            world.grid.lat,
+            move |progress| {
+                let (heights, moisture) =
+                    generate_fields(seeds, &settings, origin, &world, progress);
+                let vertices = build_vertices(&heights, &moisture, &world);
+                Generated {
+                    heights,
+                    moisture,
+                    vertices,
+                }
+            },
+        ));
     }
 
-    fn regenerate(&mut self, queue: &wgpu::Queue) {
-        let (vertices, _) = generate_mesh(self.seeds, &self.settings);
-        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+    /// Uploads the result of a finished `regenerate`; called once per frame.
+    pub fn poll_generation(&mut self, queue: &wgpu::Queue) {
+        let Some(job) = &self.generation else {
+            return;
+        };
+        match job.poll() {
+            JobStatus::Running => return,
+            JobStatus::Done(generated) => {
+                self.heights = generated.heights;
+                self.moisture = generated.moisture;
+                self.upload_vertices(queue, &generated.vertices);
+            }
+            JobStatus::Failed => {
+                eprintln!("terrain generation failed; keeping the previous heightfield");
+            }
+        }
+        self.generation = None;
     }
 
-    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
-        pass.set_pipeline(&self.pipeline);
+    /// Completed share of a `regenerate` still running in the background.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn generation_progress(&self) -> Option<f32> {
+        self.generation
+            .as_ref()
+            .map(|job| job.progress().fraction())
+    }
+
+    /// Softens the whole heightfield with `passes` blur passes. The result
+    /// lasts until the next regenerate (randomize, origin or shape change).
+    pub fn smooth(&mut self, queue: &wgpu::Queue, passes: u32) {
+        if passes == 0 {
+            return;
+        }
+        // Only the home tile wraps around the globe; other tiles have
+        // independent east and west edges.
+        let wrap = self.origin == Vec2::ZERO;
+        for _ in 0..passes {
+            blur_heights(&mut self.heights, self.world.grid, wrap);
+        }
+        self.upload_heights(queue);
+    }
+
+    /// Rerolls the terrain within `brush.radius` of `center` from fresh
+    /// seeds, easing back to the current heights and moisture over the outer
+    /// `brush.feather` so the patch stitches into its surroundings. Like
+    /// smoothing it lasts until the next regenerate. Returns false, changing
+    /// nothing, while a regenerate is running, since its result would
+    /// replace the patch.
+    pub fn regenerate_region(
+        &mut self,
+        queue: &wgpu::Queue,
+        rng: &mut impl Rng,
+        center: Vec2,
+        brush: &BrushSettings,
+    ) -> bool {
+        if self.generation.is_some() {
+            return false;
+        }
+        let seeds = NoiseSeeds::new(rng);
+        let grid = self.world.grid;
+        let (radius, amplitude) = (self.world.radius, self.world.height_amplitude);
+        let center_dir = sphere_dir(center);
+        let row_span = brush.radius / radius / std::f32::consts::PI;
+        let z0 = ((center.y - row_span).max(0.0) * (grid.lat - 1) as f32).floor() as u32;
+        let z1 = ((center.y + row_span).min(1.0) * (grid.lat - 1) as f32).ceil() as u32;
+        for z in z0..=z1 {
+            for x in 0..grid.lon {
+                let uv = grid.uv(x, z);
+                let distance = center_dir.dot(sphere_dir(uv)).clamp(-1.0, 1.0).acos() * radius;
+                let weight = brush::feathered(distance, brush.radius, brush.feather);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let world = self.origin + uv;
+                let drift = tile_drift(world);
+                let (height, moisture) =
+                    height_for_dir(sphere_dir(world), drift, seeds, &self.settings, amplitude);
+                let idx = grid.index(x, z);
+                self.heights[idx] = lerp(self.heights[idx], height, weight);
+                self.moisture[idx] = lerp(self.moisture[idx], moisture, weight);
+            }
+        }
+        let vertices = build_vertices(&self.heights, &self.moisture, &self.world);
+        // Normals also read the rows either side of the patch.
+        let rows = z0.saturating_sub(1)..=(z1 + 1).min(grid.lat - 1);
+        self.upload_vertex_rows(queue, &vertices, rows);
+        true
+    }
+
+    /// Rebuilds vertices from the CPU heightfield and uploads them.
+    fn upload_heights(&mut self, queue: &wgpu::Queue) {
+        let vertices = build_vertices(&self.heights, &self.moisture, &self.world);
+        self.upload_vertices(queue, &vertices);
+    }
+
+    /// Uploads a full grid of `vertices`, into the spare buffer and then
+    /// flipping to it when double-buffered.
+    fn upload_vertices(&mut self, queue: &wgpu::Queue, vertices: &[Vertex]) {
+        let rows = 0..=self.world.grid.lat - 1;
+        let Some(spare) = self.spare_vertex_buffer.as_mut() else {
+            self.upload_vertex_rows(queue, vertices, rows);
+            return;
+        };
+        queue.write_buffer(spare, 0, bytemuck::cast_slice(vertices));
+        std::mem::swap(&mut self.vertex_buffer, spare);
+        self.vertices_changed(rows);
+    }
+
+    /// Uploads the given latitude rows of a full grid of `vertices`. Partial
+    /// uploads always go to the buffer being drawn, since the spare may hold
+    /// stale rows.
+    fn upload_vertex_rows(
+        &mut self,
+        queue: &wgpu::Queue,
+        vertices: &[Vertex],
+        rows: RangeInclusive<u32>,
+    ) {
+        self.vertices_changed(rows.clone());
+        let grid = self.world.grid;
+        let start = grid.index(0, *rows.start());
+        let end = grid.index(0, rows.end() + 1);
+        let offset = (start * std::mem::size_of::<Vertex>()) as u64;
+        let bytes = bytemuck::cast_slice(&vertices[start..end]);
+        queue.write_buffer(&self.vertex_buffer, offset, bytes);
+    }
+
+    /// Bookkeeping after any vertex upload covering `rows` of the grid.
+    fn vertices_changed(&mut self, rows: RangeInclusive<u32>) {
+        let lon = self.world.grid.lon as usize;
+        for z in rows {
+            let start = z as usize * lon;
+            self.row_spans[z as usize] = height_span(&self.heights[start..start + lon]);
+        }
+        self.height_span = self.row_spans.iter().copied().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(low, high), (row_low, row_high)| (low.min(row_low), high.max(row_high)),
+        );
+        self.revision += 1;
+    }
+
+    pub fn grid(&self) -> Grid {
+        self.world.grid
+    }
+
+    /// Row-major CPU heightfield, `Grid::lon` samples per latitude row.
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    /// Writes one CSV row per grid vertex: grid column and row, map
+    /// coordinates, height, globe normal, moisture, temperature and biome.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn write_csv(&self, out: impl Write) -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(out);
+        writeln!(
+            out,
+            "x,z,u,v,height,normal_x,normal_y,normal_z,moisture,temperature,biome"
+        )?;
+        let vertices = build_vertices(&self.heights, &self.moisture, &self.world);
+        let grid = self.world.grid;
+        let amplitude = self.world.height_amplitude;
+        for z in 0..grid.lat {
+            for x in 0..grid.lon {
+                let v = &vertices[grid.index(x, z)];
+                let [u, map_v, height] = v.flat_pos;
+                let [nx, ny, nz] = v.normal;
+                let biome =
+                    Biome::classify(height, v.temperature, v.moisture, amplitude, &self.settings);
+                writeln!(
+                    out,
+                    "{x},{z},{u},{map_v},{height},{nx},{ny},{nz},{},{},{}",
+                    v.moisture,
+                    v.temperature,
+                    biome.label()
+                )?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Incremented every time the heightfield changes.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Map coordinates of the first terrain point hit by `ray`, tested against
+    /// the globe or the flat map depending on which one the morph is nearer.
+    pub fn pick(&self, ray: &Ray, morph: f32, rotation: f32) -> Option<Vec2> {
+        let ray = &Ray {
+            origin: self.transform.to_local(ray.origin),
+            dir: ray.dir,
+        };
+        let mut uv = None;
+        if morph < 0.5 {
+            let mut radius = self.world.radius;
+            for _ in 0..PICK_ITERATIONS {
+                let hit = globe_uv(ray.at(picking::intersect_sphere(ray, radius)?));
+                radius = self.world.radius + self.height_at(hit).max(self.water_level);
+                uv = Some(hit);
+            }
+        } else {
+            let mut height = self.water_level;
+            for _ in 0..PICK_ITERATIONS {
+                let hit = ray.at(picking::intersect_plane(ray, height)?);
+                let hit = flat_uv(hit, rotation, self.world.radius)?;
+                height = self.height_at(hit).max(self.water_level);
+                uv = Some(hit);
+            }
+        }
+        uv
+    }
+
+    /// Bilinearly sampled height at map coordinates `uv`.
+    pub fn height_at(&self, uv: Vec2) -> f32 {
+        let grid = self.world.grid;
+        let fx = uv.x.clamp(0.0, 1.0) * (grid.lon - 1) as f32;
+        let fz = uv.y.clamp(0.0, 1.0) * (grid.lat - 1) as f32;
+        let x0 = (fx.floor() as u32).min(grid.lon - 2);
+        let z0 = (fz.floor() as u32).min(grid.lat - 2);
+        let tx = fx - x0 as f32;
+        let tz = fz - z0 as f32;
+        let h = |x: u32, z: u32| self.heights[grid.index(x, z)];
+        let top = lerp(h(x0, z0), h(x0 + 1, z0), tx);
+        let bottom = lerp(h(x0, z0 + 1), h(x0 + 1, z0 + 1), tx);
+        lerp(top, bottom, tz)
+    }
+
+    /// Biased temperature at map coordinates `uv`, on the scale the biome
+    /// thresholds in `TerrainSettings` use.
+    pub fn temperature_at(&self, uv: Vec2) -> f32 {
+        let altitude = (self.height_at(uv) / self.world.height_amplitude).max(0.0);
+        let temperature = 1.0 - sphere_dir(uv).y.abs() - altitude * LAPSE_RATE;
+        (temperature + self.settings.temperature_bias).clamp(0.0, 1.0)
+    }
+
+    /// World position of the visible surface at map coordinates `uv`, placed
+    /// the way the vertex shader places it for `morph` and `rotation`. Over
+    /// water this is the sea surface rather than the seabed.
+    pub fn surface_point(&self, uv: Vec2, morph: f32, rotation: f32) -> Vec3 {
+        let t = morph.clamp(0.0, 1.0);
+        let height = self.height_at(uv).max(self.water_level);
+        let (s, c) = (rotation * t).sin_cos();
+        let pos = sphere_dir(uv) * (self.world.radius + height);
+        let globe = Vec3::new(pos.x * c + pos.z * s, pos.y, -pos.x * s + pos.z * c);
+        let u = (uv.x + rotation / std::f32::consts::TAU).rem_euclid(1.0);
+        let map = map_size(self.world.radius);
+        let flat = Vec3::new(
+            (u - 0.5) * map.x,
+            height,
+            (0.5 - uv.y.clamp(0.0, 1.0)) * map.y,
+        );
+        self.transform.to_world(globe.lerp(flat, t))
+    }
+
+    /// `samples + 1` surface points from `a` to `b`: along the great circle
+    /// on the globe, or a straight line across the flat map, whichever the
+    /// morph is nearer (the same choice `pick` makes).
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn surface_path(
+        &self,
+        a: Vec2,
+        b: Vec2,
+        samples: usize,
+        morph: f32,
+        rotation: f32,
+    ) -> Vec<Vec3> {
+        let samples = samples.max(1);
+        (0..=samples)
+            .map(|i| {
+                let t = i as f32 / samples as f32;
+                let uv = if morph < 0.5 {
+                    globe_uv(slerp_dir(sphere_dir(a), sphere_dir(b), t))
+                } else {
+                    // Interpolate in displayed coordinates so the path never
+                    // wraps around the map's seam.
+                    let shift = rotation / std::f32::consts::TAU;
+                    let display = |uv: Vec2| Vec2::new((uv.x + shift).rem_euclid(1.0), uv.y);
+                    let p = display(a).lerp(display(b), t);
+                    Vec2::new((p.x - shift).rem_euclid(1.0), p.y)
+                };
+                self.surface_point(uv, morph, rotation)
+            })
+            .collect()
+    }
+
+    /// Applies one brush dab centred on `center` to the paint layer.
+    pub fn paint(&mut self, queue: &wgpu::Queue, center: Vec2, brush: &BrushSettings) {
+        if matches!(brush.mode, BrushMode::Off | BrushMode::Regenerate) {
+            return;
+        }
+        let grid = self.world.grid;
+        let center_dir = sphere_dir(center);
+        let row_span = brush.radius / self.world.radius / std::f32::consts::PI;
+        let z0 = ((center.y - row_span).max(0.0) * (grid.lat - 1) as f32).floor() as u32;
+        let z1 = ((center.y + row_span).min(1.0) * (grid.lat - 1) as f32).ceil() as u32;
+        for z in z0..=z1 {
+            for x in 0..grid.lon {
+                let dir = sphere_dir(grid.uv(x, z));
+                let distance = center_dir.dot(dir).clamp(-1.0, 1.0).acos() * self.world.radius;
+                let weight = brush::falloff(distance, brush.radius) * brush::FLOW;
+                if weight <= 0.0 {
+                    continue;
+                }
+                let texel = &mut self.paint[grid.index(x, z)];
+                match brush.mode {
+                    BrushMode::Paint => {
+                        // Unpainted texels take the brush color outright so soft
+                        // edges fade in rather than darkening towards black.
+                        let tint = if texel[3] <= 1e-3 { 1.0 } else { weight };
+                        for (channel, target) in texel.iter_mut().zip(brush.color) {
+                            *channel += (target - *channel) * tint;
+                        }
+                        texel[3] += (brush.opacity - texel[3]) * weight;
+                    }
+                    BrushMode::Erase => texel[3] -= texel[3] * weight,
+                    BrushMode::Off | BrushMode::Regenerate => {}
+                }
+            }
+        }
+        self.upload_paint_rows(queue, z0, z1);
+    }
+
+    pub fn paint_layer(&self) -> Vec<[f32; 4]> {
+        self.paint.clone()
+    }
+
+    pub fn set_paint_layer(&mut self, queue: &wgpu::Queue, paint: Vec<[f32; 4]>) {
+        debug_assert_eq!(paint.len(), self.paint.len());
+        self.paint = paint;
+        self.upload_paint_rows(queue, 0, self.world.grid.lat - 1);
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn clear_paint(&mut self, queue: &wgpu::Queue) {
+        self.paint.fill([0.0; 4]);
+        self.upload_paint_rows(queue, 0, self.world.grid.lat - 1);
+    }
+
+    fn upload_paint_rows(&self, queue: &wgpu::Queue, first: u32, last: u32) {
+        let start = self.world.grid.index(0, first);
+        let end = self.world.grid.index(0, last + 1);
+        let bytes = paint_bytes(&self.paint[start..end]);
+        let offset = (start * std::mem::size_of::<[u8; 4]>()) as u64;
+        queue.write_buffer(&self.paint_buffer, offset, bytemuck::cast_slice(&bytes));
+    }
+
+    /// Draws with whichever `TerrainRenderer` pipeline the pass has set.
+    fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
         pass.set_bind_group(0, &self.bind_group, &[]);
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        pass.draw_indexed(0..self.index_count, 0, 0..1);
+        pass.set_vertex_buffer(1, self.paint_buffer.slice(..));
+        self.index_buffer
+            .draw_ranges(pass, self.visible.borrow().as_deref());
     }
 }
 
-fn generate_mesh(seeds: NoiseSeeds, settings: &TerrainSettings) -> (Vec<Vertex>, Vec<u32>) {
-    let continent_seed = seeds.continent;
-    let hill_seed = seeds.hill;
-    let mountain_seed = seeds.mountain;
-    let detail_seed = seeds.detail;
-    let moisture_seed = seeds.moisture;
-    let warp_seed = seeds.warp;
-
-    let mut heights = vec![0.0f32; (LAT_POINTS * LON_POINTS) as usize];
-    let mut moisture_map = vec![0.0f32; (LAT_POINTS * LON_POINTS) as usize];
-    let mut positions = vec![Vec3::ZERO; (LAT_POINTS * LON_POINTS) as usize];
-    let mut flat_positions = vec![Vec3::ZERO; (LAT_POINTS * LON_POINTS) as usize];
-    for z in 0..LAT_POINTS {
-        let v = z as f32 / (LAT_POINTS - 1) as f32;
-        let lat = v * std::f32::consts::PI;
-        let sin_lat = lat.sin();
-        let cos_lat = lat.cos();
-        for x in 0..LON_POINTS {
-            let u = x as f32 / (LON_POINTS - 1) as f32;
-            let lon = u * std::f32::consts::TAU;
-            let dir = Vec3::new(lon.cos() * sin_lat, cos_lat, lon.sin() * sin_lat);
-            let (height, moisture) = height_for_dir(
-                dir,
-                continent_seed,
-                hill_seed,
-                mountain_seed,
-                detail_seed,
-                moisture_seed,
-                warp_seed,
-                settings,
-            );
-            let idx = (z * LON_POINTS + x) as usize;
+/// Compiles the terrain shader and builds the color and id pipelines for both
+/// depth compare directions.
+fn create_pipelines(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> ([wgpu::RenderPipeline; 2], [wgpu::RenderPipeline; 2]) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("terrain shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    let build_pipeline = |label, entry_point, target, compare| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::layout(), Vertex::paint_layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[Some(target)],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth::FORMAT,
+                depth_write_enabled: true,
+                depth_compare: compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    };
+    let pipelines = depth::COMPARES.map(|compare| {
+        let target = wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+        build_pipeline("terrain pipeline", "fs_main", target, compare)
+    });
+    let id_pipelines = depth::COMPARES.map(|compare| {
+        let target = wgpu::ColorTargetState {
+            format: id_buffer::FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+        build_pipeline("terrain id pipeline", "fs_id", target, compare)
+    });
+    (pipelines, id_pipelines)
+}
+
+fn row_spans(heights: &[f32], grid: Grid) -> Vec<(f32, f32)> {
+    heights.chunks(grid.lon as usize).map(height_span).collect()
+}
+
+fn height_span(heights: &[f32]) -> (f32, f32) {
+    heights
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), &h| {
+            (low.min(h), high.max(h))
+        })
+}
+
+/// Samples the height and moisture noise at every grid vertex, advancing
+/// `progress` once per latitude row. Stops early, leaving the rest zero, if
+/// `progress` is cancelled.
+fn generate_fields(
+    seeds: NoiseSeeds,
+    settings: &TerrainSettings,
+    origin: Vec2,
+    config: &WorldConfig,
+    progress: &Progress,
+) -> (Vec<f32>, Vec<f32>) {
+    let grid = config.grid;
+    let amplitude = config.height_amplitude;
+    let mut heights = vec![0.0f32; grid.points()];
+    let mut moisture_map = vec![0.0f32; grid.points()];
+    for z in 0..grid.lat {
+        if progress.is_cancelled() {
+            break;
+        }
+        for x in 0..grid.lon {
+            let world = origin + grid.uv(x, z);
+            let drift = tile_drift(world);
+            let (height, moisture) =
+                height_for_dir(sphere_dir(world), drift, seeds, settings, amplitude);
+            let idx = grid.index(x, z);
             heights[idx] = height;
             moisture_map[idx] = moisture;
-            positions[idx] = dir * (WORLD_RADIUS + height);
-            flat_positions[idx] = Vec3::new(u, v, height);
+        }
+        progress.advance(1);
+    }
+    (heights, moisture_map)
+}
+
+/// Builds the vertex data (positions, normals, temperature) for a heightfield.
+fn build_vertices(heights: &[f32], moisture_map: &[f32], world: &WorldConfig) -> Vec<Vertex> {
+    let grid = world.grid;
+    let radius = world.radius;
+    let mut temperature_map = vec![0.0f32; grid.points()];
+    let mut positions = vec![Vec3::ZERO; grid.points()];
+    let mut flat_positions = vec![Vec3::ZERO; grid.points()];
+    for z in 0..grid.lat {
+        for x in 0..grid.lon {
+            let uv = grid.uv(x, z);
+            let dir = sphere_dir(uv);
+            let idx = grid.index(x, z);
+            let height = heights[idx];
+            // Sea level is the reference; the seabed is not colder than the surface.
+            let altitude = (height / world.height_amplitude).max(0.0);
+            temperature_map[idx] = 1.0 - dir.y.abs() - altitude * LAPSE_RATE;
+            positions[idx] = dir * (radius + height);
+            flat_positions[idx] = uv.extend(height);
         }
     }
 
     let mut normals = vec![Vec3::ZERO; positions.len()];
-    let lon_last = LON_POINTS - 1;
-    for z in 0..LAT_POINTS {
-        for x in 0..LON_POINTS {
-            let idx = (z * LON_POINTS + x) as usize;
-            if z == 0 || z == LAT_POINTS - 1 {
+    let lon_last = grid.lon - 1;
+    for z in 0..grid.lat {
+        for x in 0..grid.lon {
+            let idx = grid.index(x, z);
+            if z == 0 || z == grid.lat - 1 {
                 normals[idx] = positions[idx].normalize_or_zero();
                 continue;
             }
 
             let x_left = if x == 0 { lon_last - 1 } else { x - 1 };
             let x_right = if x == lon_last { 1 } else { x + 1 };
-            let left = positions[(z * LON_POINTS + x_left) as usize];
-            let right = positions[(z * LON_POINTS + x_right) as usize];
-            let down = positions[((z - 1) * LON_POINTS + x) as usize];
-            let up = positions[((z + 1) * LON_POINTS + x) as usize];
+            let left = positions[grid.index(x_left, z)];
+            let right = positions[grid.index(x_right, z)];
+            let down = positions[grid.index(x, z - 1)];
+            let up = positions[grid.index(x, z + 1)];
             let tangent = right - left;
             let bitangent = up - down;
             let normal = tangent.cross(bitangent).normalize_or_zero();
@@ -337,48 +1368,202 @@ fn generate_mesh(seeds: NoiseSeeds, settings: &TerrainSettings) -> (Vec<Vertex>,
         }
     }
 
-    let mut vertices = Vec::with_capacity((LAT_POINTS * LON_POINTS) as usize);
+    let mut vertices = Vec::with_capacity(grid.points());
     for idx in 0..positions.len() {
-        let height = heights[idx];
-        let color = color_from_height(
-            height,
-            positions[idx].normalize_or_zero(),
-            moisture_map[idx],
-            settings,
-        );
         vertices.push(Vertex {
             pos: positions[idx].into(),
             normal: normals[idx].into(),
-            color,
             flat_pos: flat_positions[idx].into(),
+            moisture: moisture_map[idx],
+            temperature: temperature_map[idx],
         });
     }
+    vertices
+}
 
-    let mut indices = Vec::with_capacity(((LAT_POINTS - 1) * (LON_POINTS - 1) * 6) as usize);
-    for z in 0..LAT_POINTS - 1 {
-        for x in 0..LON_POINTS - 1 {
-            let i0 = z * LON_POINTS + x;
+fn generate_indices(grid: Grid) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(((grid.lat - 1) * (grid.lon - 1) * 6) as usize);
+    for z in 0..grid.lat - 1 {
+        for x in 0..grid.lon - 1 {
+            let i0 = z * grid.lon + x;
             let i1 = i0 + 1;
-            let i2 = i0 + LON_POINTS;
+            let i2 = i0 + grid.lon;
             let i3 = i2 + 1;
             indices.extend_from_slice(&[i0, i1, i2, i1, i3, i2]);
         }
     }
+    indices
+}
+
+/// Builds a reference bump with `indices` and checks it faces outward on the
+/// globe and upward on the flat map, and that its vertex normals agree. A
+/// change to the index order or vertex placement that turns the terrain
+/// inside out then fails at startup in debug builds instead of silently
+/// culling everything.
+fn debug_assert_reference_winding(indices: &[u32], world: &WorldConfig) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    // Gentle hill on the equator, a twentieth of the radius tall.
+    let (grid, radius) = (world.grid, world.radius);
+    let peak = sphere_dir(Vec2::splat(0.5));
+    let heights: Vec<f32> = (0..grid.lat)
+        .flat_map(|z| {
+            (0..grid.lon).map(move |x| {
+                let offset = sphere_dir(grid.uv(x, z)) - peak;
+                radius * 0.05 * (-4.0 * offset.length_squared()).exp()
+            })
+        })
+        .collect();
+    let vertices = build_vertices(&heights, &vec![0.0; heights.len()], world);
+
+    let globe: Vec<Vec3> = vertices.iter().map(|v| Vec3::from(v.pos)).collect();
+    mesh::debug_assert_winding("terrain globe", &globe, indices, |centroid| centroid);
+    let map = map_size(radius);
+    let flat: Vec<Vec3> = vertices
+        .iter()
+        .map(|v| {
+            let [u, v, height] = v.flat_pos;
+            Vec3::new((u - 0.5) * map.x, height, (0.5 - v) * map.y)
+        })
+        .collect();
+    mesh::debug_assert_winding("terrain map", &flat, indices, |_| Vec3::Y);
+    assert!(
+        vertices
+            .iter()
+            .all(|v| Vec3::from(v.normal).dot(Vec3::from(v.pos)) > 0.0),
+        "terrain normals point into the globe"
+    );
+}
+
+/// One pass of a separable `[1, 2, 1] / 4` blur. Longitude wraps when
+/// `wrap` is set (the first and last columns are the same meridian); the pole
+/// rows, which collapse to single points on the globe, are flattened to their
+/// mean so the blur cannot pinch them.
+fn blur_heights(heights: &mut [f32], grid: Grid, wrap: bool) {
+    let lon = grid.lon as usize;
+    let lat = grid.lat as usize;
+    let period = lon - 1;
+    let mut rows = vec![0.0f32; heights.len()];
+    for z in 0..lat {
+        let row = &heights[z * lon..(z + 1) * lon];
+        for x in 0..lon {
+            let (left, right) = if wrap {
+                ((x + period - 1) % period, (x + 1) % period)
+            } else {
+                (x.saturating_sub(1), (x + 1).min(lon - 1))
+            };
+            rows[z * lon + x] = (row[left] + 2.0 * row[x] + row[right]) * 0.25;
+        }
+    }
+    for z in 0..lat {
+        let down = z.saturating_sub(1);
+        let up = (z + 1).min(lat - 1);
+        for x in 0..lon {
+            heights[z * lon + x] =
+                (rows[down * lon + x] + 2.0 * rows[z * lon + x] + rows[up * lon + x]) * 0.25;
+        }
+    }
+    for z in [0, lat - 1] {
+        let row = &mut heights[z * lon..(z + 1) * lon];
+        let mean = row.iter().sum::<f32>() / lon as f32;
+        row.fill(mean);
+    }
+}
+
+pub fn paint_bytes(paint: &[[f32; 4]]) -> Vec<[u8; 4]> {
+    paint
+        .iter()
+        .map(|texel| texel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        .collect()
+}
+
+/// Inverse of `sphere_dir` for a point on (or above) the globe.
+fn globe_uv(pos: Vec3) -> Vec2 {
+    let dir = pos.normalize_or_zero();
+    let lon = dir.z.atan2(dir.x).rem_euclid(std::f32::consts::TAU);
+    let lat = dir.y.clamp(-1.0, 1.0).acos();
+    Vec2::new(lon / std::f32::consts::TAU, lat / std::f32::consts::PI)
+}
+
+/// Width and depth of the flat map for a globe of `radius`: the equator and a
+/// meridian unrolled, so the globe-to-map morph preserves distances along
+/// them.
+pub fn map_size(radius: f32) -> Vec2 {
+    Vec2::new(
+        radius * std::f32::consts::TAU,
+        radius * std::f32::consts::PI,
+    )
+}
+
+/// Inverse of the shader's flat-map placement, undoing the map rotation.
+fn flat_uv(pos: Vec3, rotation: f32, radius: f32) -> Option<Vec2> {
+    let map = map_size(radius);
+    let display_u = pos.x / map.x + 0.5;
+    let v = 0.5 - pos.z / map.y;
+    if !(0.0..=1.0).contains(&display_u) || !(0.0..=1.0).contains(&v) {
+        return None;
+    }
+    let u = (display_u - rotation / std::f32::consts::TAU).rem_euclid(1.0);
+    Some(Vec2::new(u, v))
+}
+
+/// Spherical interpolation between unit vectors, falling back to a
+/// normalized lerp when they are (nearly) parallel or opposite.
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+fn slerp_dir(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    let angle = a.dot(b).clamp(-1.0, 1.0).acos();
+    let sin = angle.sin();
+    if sin < 1e-4 {
+        return a.lerp(b, t).try_normalize().unwrap_or(a);
+    }
+    (a * ((1.0 - t) * angle).sin() + b * (t * angle).sin()) / sin
+}
+
+fn sphere_dir(uv: Vec2) -> Vec3 {
+    let lon = uv.x * std::f32::consts::TAU;
+    let lat = uv.y * std::f32::consts::PI;
+    let sin_lat = lat.sin();
+    Vec3::new(lon.cos() * sin_lat, lat.cos(), lon.sin() * sin_lat)
+}
 
-    (vertices, indices)
+/// Noise-domain offset for a world-space map coordinate. Zero inside the home
+/// tile so the default globe keeps its longitude wrap; outside it the sample
+/// drifts continuously so east and west neighbours differ but agree column for
+/// column on their shared border. Tiling is east/west only: a north or south
+/// neighbour meets this tile at a pole row, where every vertex is the one pole
+/// sample, so that border is a single height and the rows beside it do not
+/// continue each other.
+fn tile_drift(world: Vec2) -> Vec3 {
+    let outside = world - world.clamp(Vec2::ZERO, Vec2::ONE);
+    Vec3::new(outside.x, 0.0, outside.y) * TILE_DRIFT
 }
 
 fn height_for_dir(
     dir: Vec3,
-    continent_seed: u32,
-    hill_seed: u32,
-    mountain_seed: u32,
-    detail_seed: u32,
-    moisture_seed: u32,
-    warp_seed: u32,
+    drift: Vec3,
+    seeds: NoiseSeeds,
     settings: &TerrainSettings,
+    amplitude: f32,
 ) -> (f32, f32) {
-    let warped = (dir + warp_dir(dir, warp_seed)).normalize_or_zero();
+    let continent_seed = seeds.continent;
+    let hill_seed = seeds.hill;
+    let mountain_seed = seeds.mountain;
+    let detail_seed = seeds.detail;
+    let moisture_seed = seeds.moisture;
+    // Sampling noise at `pos * freq` shrinks features by `freq`. The warps
+    // sample at the same scale and their offsets shrink with it, so the
+    // whole pattern scales together.
+    let freq = 1.0 / settings.feature_size.max(1e-3);
+    let warp = warp_dir(dir, WARP_FREQ * freq, seeds.warp) * (0.35 / freq);
+    let mut warped = (dir + warp).normalize_or_zero();
+    if settings.warp_strength > 0.0 {
+        // Second warp layer on top of the built-in one. Its seed is derived
+        // from the warp seed so it stays deterministic per terrain.
+        let offset = warp_dir(warped, settings.warp_scale * freq, seeds.warp ^ 0x5bd1_e995);
+        warped = (warped + offset * (settings.warp_strength / freq)).normalize_or_zero();
+    }
+    let warped = (warped + drift) * freq;
     let continent = fbm(warped * CONTINENT_FREQ, continent_seed, 5, 2.05, 0.5) * 0.85
         + fbm(warped * (CONTINENT_FREQ * 0.5), continent_seed ^ 0x9e37, 3, 2.2, 0.5) * 0.15;
     let base = continent - settings.sea_threshold;
@@ -391,66 +1576,25 @@ fn height_for_dir(
             + fbm(warped * 0.25, moisture_seed ^ 0x85eb, 2, 2.0, 0.5) * 0.2,
     );
 
-    let mut height = base * HEIGHT_AMPLITUDE;
+    let mut height = base * amplitude;
     if height > 0.0 {
-        height += settings.land_elevation_bias * HEIGHT_AMPLITUDE;
+        height += settings.land_elevation_bias * amplitude;
         let inland = smoothstep(0.08, 0.45, base);
-        height += (hills - 0.5) * HEIGHT_AMPLITUDE * 0.35 * land_mask;
-        height += ridges.powf(2.0) * HEIGHT_AMPLITUDE * 0.9 * inland;
-        height += detail * HEIGHT_AMPLITUDE * 0.07;
+        height += (hills - 0.5) * amplitude * 0.35 * land_mask;
+        height += ridges.powf(2.0) * amplitude * 0.9 * inland;
+        height += detail * amplitude * 0.07;
     } else {
         let depth = (-base).max(0.0);
         let shelf = smoothstep(0.03, 0.2, depth);
-        let ocean = -depth.powf(1.35) * HEIGHT_AMPLITUDE * 0.85;
-        let shelf_lift = (1.0 - shelf) * HEIGHT_AMPLITUDE * 0.08;
-        height = ocean + shelf_lift + detail * HEIGHT_AMPLITUDE * 0.04;
+        let ocean = -depth.powf(1.35) * amplitude * 0.85;
+        let shelf_lift = (1.0 - shelf) * amplitude * 0.08;
+        height = ocean + shelf_lift + detail * amplitude * 0.04;
         height = height.min(-0.15);
     }
 
     (height, moisture)
 }
 
-fn color_from_height(
-    height: f32,
-    dir: Vec3,
-    moisture: f32,
-    settings: &TerrainSettings,
-) -> [f32; 3] {
-    let h = height / HEIGHT_AMPLITUDE;
-    if h < -0.45 {
-        return [0.04, 0.12, 0.24];
-    }
-    if h < -0.2 {
-        return [0.03, 0.1, 0.22];
-    }
-    if h < 0.0 {
-        return [0.06, 0.22, 0.35];
-    }
-    if h < settings.beach_max_height {
-        return [0.78, 0.72, 0.54];
-    }
-
-    let latitude = dir.y.abs();
-    let temp = (1.0 - latitude - h * 0.45 + settings.temperature_bias).clamp(0.0, 1.0);
-    if h > 0.85 || temp < 0.15 {
-        return [0.9, 0.92, 0.96];
-    }
-    if h > 0.65 {
-        return [0.48, 0.46, 0.44];
-    }
-    if temp < 0.3 {
-        return [0.62, 0.66, 0.6];
-    }
-
-    if moisture < settings.desert_moisture_max {
-        [0.8, 0.72, 0.45]
-    } else if moisture < settings.semi_arid_moisture_max {
-        [0.22, 0.56, 0.28]
-    } else {
-        [0.08, 0.43, 0.22]
-    }
-}
-
 fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
     let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
     t * t * (3.0 - 2.0 * t)
@@ -492,11 +1636,11 @@ fn ridged_fbm(pos: Vec3, seed: u32, octaves: u32, lacunarity: f32, gain: f32) ->
     (sum / norm).clamp(0.0, 1.0)
 }
 
-fn warp_dir(dir: Vec3, seed: u32) -> Vec3 {
-    let wx = fbm(dir * WARP_FREQ, seed, 3, 2.0, 0.5);
-    let wy = fbm(dir * WARP_FREQ + Vec3::splat(12.7), seed ^ 0x27d4, 3, 2.0, 0.5);
-    let wz = fbm(dir * WARP_FREQ + Vec3::splat(31.4), seed ^ 0x1656, 3, 2.0, 0.5);
-    Vec3::new(wx, wy, wz) * 0.35
+fn warp_dir(dir: Vec3, freq: f32, seed: u32) -> Vec3 {
+    let wx = fbm(dir * freq, seed, 3, 2.0, 0.5);
+    let wy = fbm(dir * freq + Vec3::splat(12.7), seed ^ 0x27d4, 3, 2.0, 0.5);
+    let wz = fbm(dir * freq + Vec3::splat(31.4), seed ^ 0x1656, 3, 2.0, 0.5);
+    Vec3::new(wx, wy, wz)
 }
 
 fn sample_noise_3d(x: f32, y: f32, z: f32, seed: u32) -> f32 {
@@ -541,3 +1685,143 @@ fn hash3(x: i32, y: i32, z: i32, seed: u32) -> f32 {
 fn lerp(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::DEFAULT_RADIUS;
+    use rand::SeedableRng;
+    use std::f32::consts::{PI, TAU};
+
+    fn fields_at(origin: Vec2, world: &WorldConfig) -> Vec<f32> {
+        let seeds = NoiseSeeds::new(&mut rand::rngs::StdRng::seed_from_u64(7));
+        let settings = TerrainSettings::default();
+        let progress = Progress::new(world.grid.lat);
+        generate_fields(seeds, &settings, origin, world, &progress).0
+    }
+
+    #[test]
+    fn adjacent_tiles_share_their_border() {
+        let world = WorldConfig {
+            grid: Grid::new(32),
+            ..WorldConfig::default()
+        };
+        let grid = world.grid;
+        let column = |heights: &[f32], x: u32| -> Vec<f32> {
+            (0..grid.lat).map(|z| heights[grid.index(x, z)]).collect()
+        };
+        let (first, last) = (0, grid.lon - 1);
+        let home = fields_at(Vec2::ZERO, &world);
+        let east = fields_at(Vec2::X, &world);
+        let west = fields_at(-Vec2::X, &world);
+        assert_eq!(column(&home, last), column(&east, first));
+        assert_eq!(column(&west, last), column(&home, first));
+        // The tiles must still differ away from the border.
+        assert_ne!(column(&home, last / 2), column(&east, last / 2));
+
+        // North and south neighbours only share the collapsed pole row: it is
+        // one height across, and the first real row beyond it is not the
+        // home tile's row seen across the pole.
+        let row = |heights: &[f32], z: u32| -> Vec<f32> {
+            (0..grid.lon).map(|x| heights[grid.index(x, z)]).collect()
+        };
+        let across_pole = |heights: &[f32], z: u32| -> Vec<f32> {
+            let half = (grid.lon - 1) / 2;
+            (0..grid.lon)
+                .map(|x| heights[grid.index((x + half) % (grid.lon - 1), z)])
+                .collect()
+        };
+        let (top, bottom) = (0, grid.lat - 1);
+        let south = fields_at(Vec2::Y, &world);
+        let north = fields_at(-Vec2::Y, &world);
+        for (pole, heights) in [(row(&home, bottom), &south), (row(&north, bottom), &home)] {
+            assert_eq!(pole, row(heights, top));
+            let spread = pole.iter().fold(0.0f32, |m, &h| m.max((h - pole[0]).abs()));
+            assert!(spread < 1e-3, "pole row varies by {spread}");
+        }
+        assert_ne!(row(&south, top + 1), across_pole(&home, bottom - 1));
+        assert_ne!(row(&home, top + 1), across_pole(&north, bottom - 1));
+    }
+
+    #[test]
+    fn placement_follows_the_radius() {
+        for radius in [DEFAULT_RADIUS, 2.0 * DEFAULT_RADIUS] {
+            let world = WorldConfig {
+                grid: Grid::new(16),
+                radius,
+                ..WorldConfig::default()
+            };
+            let map = map_size(radius);
+            assert_eq!(map, Vec2::new(TAU, PI) * radius);
+
+            let flat = vec![0.0; world.grid.points()];
+            for vertex in build_vertices(&flat, &flat, &world) {
+                let error = (Vec3::from(vertex.pos).length() - radius).abs();
+                assert!(error < radius * 1e-5, "{error} off radius {radius}");
+            }
+
+            // Placed on the map the way `surface_point` places it.
+            for rotation in [0.0, 1.0, -2.5, 4.0] {
+                for uv in [Vec2::new(0.1, 0.2), Vec2::splat(0.5), Vec2::new(0.9, 0.75)] {
+                    let u = (uv.x + rotation / TAU).rem_euclid(1.0);
+                    let pos = Vec3::new((u - 0.5) * map.x, 0.0, (0.5 - uv.y) * map.y);
+                    let back = flat_uv(pos, rotation, radius).unwrap();
+                    assert!((back - uv).abs().max_element() < 1e-5, "{back} vs {uv}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reference_globe_winds_outward() {
+        let world = WorldConfig {
+            grid: Grid::new(32),
+            ..WorldConfig::default()
+        };
+        debug_assert_reference_winding(&generate_indices(world.grid), &world);
+    }
+
+    // The check compiles out of release builds, and the panic with it.
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "winds clockwise")]
+    fn flipped_reference_globe_fails_the_winding_check() {
+        let world = WorldConfig {
+            grid: Grid::new(32),
+            ..WorldConfig::default()
+        };
+        let mut indices = generate_indices(world.grid);
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        debug_assert_reference_winding(&indices, &world);
+    }
+
+    #[test]
+    fn background_generation_matches_generating_in_place() {
+        let world = WorldConfig {
+            grid: Grid::new(24),
+            ..WorldConfig::default()
+        };
+        let seeds = NoiseSeeds::new(&mut rand::rngs::StdRng::seed_from_u64(11));
+        let settings = TerrainSettings::default();
+        let origin = Vec2::new(2.0, 0.0);
+        let progress = Progress::new(world.grid.lat);
+        let in_place = generate_fields(seeds, &settings, origin, &world, &progress);
+
+        // The same work `regenerate` hands its job.
+        let job = BackgroundJob::spawn("test generation", world.grid.lat, move |progress| {
+            generate_fields(seeds, &settings, origin, &world, progress)
+        });
+        let background = loop {
+            match job.poll() {
+                JobStatus::Running => std::thread::sleep(std::time::Duration::from_millis(1)),
+                JobStatus::Done(fields) => break fields,
+                JobStatus::Failed => panic!("generation job failed"),
+            }
+        };
+        assert_eq!(background, in_place);
+        assert_eq!(job.progress().fraction(), 1.0);
+        assert_eq!(progress.fraction(), 1.0);
+    }
+}
@@ -0,0 +1,79 @@
+/// Blend values closer than this to the target snap onto it, so the morph
+/// settles on exactly 0 or 1 instead of creeping towards it for many frames.
+pub const SNAP_EPSILON: f32 = 1e-3;
+pub const DEFAULT_SPEED: f32 = 2.5;
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+pub const DEFAULT_SECONDS: f32 = 1.2;
+
+/// How the globe-to-map blend moves towards its target each frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MapTransition {
+    /// Covers `1 - exp(-speed * dt)` of the remaining way per frame: fast at
+    /// first, easing in at the end.
+    Exponential { speed: f32 },
+    /// Moves at a constant rate, so a full flatten takes `seconds` at any
+    /// frame rate. Reversing midway takes the same share of that time.
+    Fixed { seconds: f32 },
+}
+
+impl Default for MapTransition {
+    fn default() -> Self {
+        MapTransition::Exponential {
+            speed: DEFAULT_SPEED,
+        }
+    }
+}
+
+impl MapTransition {
+    /// Blend after `dt` seconds moving from `blend` towards `target`.
+    pub fn step(self, blend: f32, target: f32, dt: f32) -> f32 {
+        let next = match self {
+            MapTransition::Exponential { speed } => {
+                blend + (target - blend) * (1.0 - (-speed * dt).exp())
+            }
+            MapTransition::Fixed { seconds } => {
+                let max_step = dt / seconds.max(1e-3);
+                blend + (target - blend).clamp(-max_step, max_step)
+            }
+        };
+        if (target - next).abs() < SNAP_EPSILON {
+            target
+        } else {
+            next.clamp(0.0, 1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds a full flatten takes ticking at `dt`, and the blend it ends on.
+    fn settle(transition: MapTransition, dt: f32) -> (f32, f32) {
+        let (mut blend, mut elapsed) = (0.0, 0.0);
+        while blend != 1.0 && elapsed < 60.0 {
+            blend = transition.step(blend, 1.0, dt);
+            elapsed += dt;
+        }
+        (elapsed, blend)
+    }
+
+    #[test]
+    fn settle_time_is_frame_rate_independent() {
+        let transitions = [
+            MapTransition::default(),
+            MapTransition::Fixed {
+                seconds: DEFAULT_SECONDS,
+            },
+        ];
+        for transition in transitions {
+            let (slow, slow_end) = settle(transition, 1.0 / 60.0);
+            let (fast, fast_end) = settle(transition, 1.0 / 240.0);
+            assert_eq!((slow_end, fast_end), (1.0, 1.0), "{transition:?}");
+            assert!(
+                (slow - fast).abs() <= 1.0 / 60.0,
+                "{transition:?}: {slow} s at 60 Hz but {fast} s at 240 Hz"
+            );
+        }
+    }
+}
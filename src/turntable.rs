@@ -0,0 +1,204 @@
+use glam::Vec3;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::input::{self, CameraPose};
+
+pub const DEFAULT_FRAMES: u32 = 120;
+pub const DEFAULT_SIZE: [u32; 2] = [1280, 720];
+pub const DEFAULT_ELEVATION_DEGREES: f32 = 25.0;
+pub const DEFAULT_DIRECTORY: &str = "turntable";
+/// Animation time between frames, so waves and weather play back at the
+/// right speed when the frames are encoded at 30 fps.
+pub const FRAME_TIME: f32 = 1.0 / 30.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TurntableSettings {
+    /// Frames in the full 360° orbit; the last stops one step short of the
+    /// first so the sequence loops.
+    pub frames: u32,
+    pub size: [u32; 2],
+    /// Camera height above the target's horizontal plane, in degrees.
+    pub elevation_degrees: f32,
+}
+
+impl Default for TurntableSettings {
+    fn default() -> Self {
+        Self {
+            frames: DEFAULT_FRAMES,
+            size: DEFAULT_SIZE,
+            elevation_degrees: DEFAULT_ELEVATION_DEGREES,
+        }
+    }
+}
+
+impl TurntableSettings {
+    /// Parses `FRAMES[,WIDTHxHEIGHT[,ELEVATION]]`, keeping the defaults for
+    /// anything left out.
+    pub fn parse(value: &str) -> Result<Self, Box<dyn Error>> {
+        let mut settings = Self::default();
+        let mut parts = value.split(',');
+        if let Some(frames) = parts.next() {
+            settings.frames = frames.trim().parse()?;
+        }
+        if let Some(size) = parts.next() {
+            let (width, height) = size
+                .split_once('x')
+                .ok_or_else(|| format!("expected WIDTHxHEIGHT but got {size:?}"))?;
+            settings.size = [width.trim().parse()?, height.trim().parse()?];
+        }
+        if let Some(elevation) = parts.next() {
+            settings.elevation_degrees = elevation.trim().parse()?;
+        }
+        if parts.next().is_some() {
+            return Err("expected FRAMES[,WIDTHxHEIGHT[,ELEVATION]]".into());
+        }
+        if settings.frames == 0 || settings.size.contains(&0) {
+            return Err("turntable frames and sides must be positive".into());
+        }
+        Ok(settings)
+    }
+}
+
+/// A turntable being rendered, one frame per tick. The orbit starts at the
+/// camera's heading and distance when it began and turns about its target.
+pub struct Turntable {
+    settings: TurntableSettings,
+    directory: PathBuf,
+    pose: CameraPose,
+    /// Animation time of frame 0; each later frame adds `FRAME_TIME`.
+    start_time: f32,
+    next: u32,
+}
+
+/// One frame of a turntable: where to render it from and where to save it.
+pub struct Frame {
+    pub eye: Vec3,
+    pub time: f32,
+    pub path: PathBuf,
+}
+
+impl Turntable {
+    /// Creates `directory` if needed and starts at frame 0.
+    pub fn new(
+        settings: TurntableSettings,
+        directory: &Path,
+        pose: CameraPose,
+        time: f32,
+    ) -> std::io::Result<Self> {
+        std::fs::create_dir_all(directory)?;
+        Ok(Self {
+            settings,
+            directory: directory.to_owned(),
+            pose,
+            start_time: time,
+            next: 0,
+        })
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.settings.size
+    }
+
+    /// The next frame to render, or `None` once the orbit is complete.
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        let index = self.next;
+        if index >= self.settings.frames {
+            return None;
+        }
+        self.next += 1;
+        let turn = index as f32 / self.settings.frames as f32;
+        let yaw = self.pose.yaw + turn * std::f32::consts::TAU;
+        // Looking down at the target from above means a negative pitch.
+        let pitch = -self.settings.elevation_degrees.to_radians();
+        let forward = input::orbit_forward(yaw, pitch);
+        Some(Frame {
+            eye: self.pose.target - forward * self.pose.orbit_radius,
+            time: self.start_time + index as f32 * FRAME_TIME,
+            path: self.directory.join(format!("frame_{index:04}.png")),
+        })
+    }
+
+    /// Share of the frames rendered so far.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn fraction(&self) -> f32 {
+        self.next as f32 / self.settings.frames.max(1) as f32
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keeps_the_defaults_for_missing_parts() {
+        let parse = |value| TurntableSettings::parse(value).unwrap();
+        let frames = TurntableSettings {
+            frames: 36,
+            ..TurntableSettings::default()
+        };
+        assert_eq!(parse("36"), frames);
+        let sized = TurntableSettings {
+            size: [640, 480],
+            ..frames
+        };
+        assert_eq!(parse("36, 640x480"), sized);
+        let raised = TurntableSettings {
+            elevation_degrees: 60.0,
+            ..sized
+        };
+        assert_eq!(parse("36,640x480,60"), raised);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_settings() {
+        let malformed = ["", "0", "x", "12,640", "12,0x480", "1,x2", "1,2x2,3,4"];
+        for value in malformed {
+            let result = TurntableSettings::parse(value);
+            assert!(result.is_err(), "accepted `{value}`");
+        }
+    }
+
+    #[test]
+    fn frames_orbit_evenly_and_step_the_clock() {
+        let directory = std::env::temp_dir().join(format!("turntable-test-{}", std::process::id()));
+        let settings = TurntableSettings {
+            frames: 8,
+            ..TurntableSettings::default()
+        };
+        let pose = CameraPose {
+            yaw: 0.3,
+            pitch: 0.0,
+            orbit_radius: 40.0,
+            target: Vec3::new(1.0, 2.0, 3.0),
+        };
+        let mut turntable = Turntable::new(settings, &directory, pose, 5.0).unwrap();
+        assert!(directory.is_dir());
+        let frames: Vec<Frame> = std::iter::from_fn(|| turntable.next_frame()).collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(frames.len(), 8);
+        assert_eq!(turntable.fraction(), 1.0);
+
+        let heading = |frame: &Frame| (frame.eye - pose.target).with_y(0.0).normalize();
+        let step = std::f32::consts::TAU / 8.0;
+        let rise = 40.0 * DEFAULT_ELEVATION_DEGREES.to_radians().sin();
+        let start = -input::orbit_forward(pose.yaw, 0.0).with_y(0.0).normalize();
+        assert!(heading(&frames[0]).distance(start) < 1e-4);
+        for (index, frame) in frames.iter().enumerate() {
+            let name = format!("frame_{index:04}.png");
+            assert_eq!(frame.path, directory.join(name));
+            assert!((frame.time - (5.0 + index as f32 * FRAME_TIME)).abs() < 1e-5);
+            assert!(((frame.eye - pose.target).length() - 40.0).abs() < 1e-3);
+            // Raised by the elevation, at evenly spaced headings that close
+            // the loop back to the first frame.
+            assert!((frame.eye.y - pose.target.y - rise).abs() < 1e-3);
+            let next = &frames[(index + 1) % frames.len()];
+            let turned = heading(frame).dot(heading(next)).clamp(-1.0, 1.0).acos();
+            assert!((turned - step).abs() < 1e-4, "frame {index}: {turned}");
+        }
+    }
+}
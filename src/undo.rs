@@ -0,0 +1,92 @@
+/// Bounded undo/redo history of whole-state snapshots.
+///
+/// Edits are bracketed by `begin` and `commit`: the snapshot is taken once at
+/// `begin` and repeated calls before `commit` are ignored, so everything that
+/// happens in between (e.g. a whole brush stroke) becomes a single entry.
+pub struct UndoStack<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    pending: Option<T>,
+    limit: usize,
+}
+
+impl<T> UndoStack<T> {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: None,
+            limit: limit.max(1),
+        }
+    }
+
+    pub fn begin(&mut self, snapshot: impl FnOnce() -> T) {
+        if self.pending.is_none() {
+            self.pending = Some(snapshot());
+        }
+    }
+
+    pub fn commit(&mut self) {
+        if let Some(snapshot) = self.pending.take() {
+            if self.undo.len() == self.limit {
+                self.undo.remove(0);
+            }
+            self.undo.push(snapshot);
+            self.redo.clear();
+        }
+    }
+
+    /// Records a single-step edit, equivalent to `begin` followed by `commit`.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn record(&mut self, snapshot: T) {
+        self.begin(|| snapshot);
+        self.commit();
+    }
+
+    /// Swaps `current` for the most recent snapshot.
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        Some(next)
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stroke_is_one_entry() {
+        let mut history = UndoStack::new(8);
+        let mut layer = vec![0u8; 4];
+        history.begin(|| layer.clone());
+        for dab in 0..20 {
+            // Every dab of the stroke asks for a snapshot; only the first counts.
+            history.begin(|| layer.clone());
+            layer[dab % 4] += 1;
+        }
+        history.commit();
+        assert_eq!(history.undo_len(), 1);
+
+        let restored = history.undo(layer.clone()).unwrap();
+        assert_eq!(restored, vec![0; 4]);
+        assert!(history.undo(restored).is_none());
+        assert_eq!(history.redo_len(), 1);
+    }
+}
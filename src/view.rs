@@ -0,0 +1,25 @@
+use glam::{Mat4, Vec3};
+
+use crate::depth::DepthMode;
+
+/// Per-frame camera and lighting state shared by every scene layer.
+#[derive(Copy, Clone, Debug)]
+pub struct SceneView {
+    pub view_proj: Mat4,
+    pub eye: Vec3,
+    pub morph: f32,
+    pub rotation: f32,
+    pub sun_dir: Vec3,
+    /// Terrain lighting model parameters, from `SunSettings::lighting_uniform`.
+    pub lighting: [f32; 4],
+    /// Hemisphere ambient sky and ground colors, from
+    /// `SunSettings::ambient_uniform`.
+    pub ambient: [[f32; 4]; 2],
+    pub depth_mode: DepthMode,
+    /// Skip grid quads that cannot be seen past the globe's horizon. Only
+    /// applies while the globe is fully rounded.
+    pub horizon_cull: bool,
+    pub far: f32,
+    /// Seconds of animation time, driving the water waves.
+    pub time: f32,
+}
@@ -0,0 +1,128 @@
+use glam::Vec2;
+
+/// Offscreen color target the scene renders into when it is embedded in an
+/// egui layout rather than owning the window. The texture is registered with
+/// the egui renderer, so any `egui::Image` can show it.
+pub struct SceneViewport {
+    view: wgpu::TextureView,
+    texture_id: egui::TextureId,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+    /// Last layout reported by `show`.
+    pub layout: ViewportLayout,
+}
+
+/// Where the scene image ended up in the egui layout this frame.
+#[derive(Copy, Clone, Debug)]
+pub struct ViewportLayout {
+    /// Image size in physical pixels; the color target follows it.
+    pub size: [u32; 2],
+    /// Top-left corner of the image in physical window pixels.
+    pub origin: Vec2,
+    /// Image rect in egui points, for clipping overlays drawn over it.
+    pub rect: egui::Rect,
+    /// Pointer input goes to the scene only while this is set.
+    pub hovered: bool,
+    /// Keyboard input goes to the scene only while this is set.
+    pub focused: bool,
+}
+
+impl SceneViewport {
+    pub fn new(
+        device: &wgpu::Device,
+        renderer: &mut egui_wgpu::Renderer,
+        format: wgpu::TextureFormat,
+        size: [u32; 2],
+    ) -> Self {
+        let view = create_view(device, format, size);
+        let texture_id = renderer.register_native_texture(device, &view, wgpu::FilterMode::Linear);
+        Self {
+            view,
+            texture_id,
+            format,
+            size,
+            layout: ViewportLayout {
+                size,
+                origin: Vec2::ZERO,
+                rect: egui::Rect::NOTHING,
+                hovered: false,
+                focused: false,
+            },
+        }
+    }
+
+    /// Reallocates the color target and points the egui texture at it.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        renderer: &mut egui_wgpu::Renderer,
+        size: [u32; 2],
+    ) {
+        if size == self.size {
+            return;
+        }
+        self.view = create_view(device, self.format, size);
+        renderer.update_egui_texture_from_wgpu_texture(
+            device,
+            &self.view,
+            wgpu::FilterMode::Linear,
+            self.texture_id,
+        );
+        self.size = size;
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn texture_id(&self) -> egui::TextureId {
+        self.texture_id
+    }
+}
+
+/// Fills the remaining space of `ui` with the scene image. Clicking or
+/// dragging on it gives it keyboard focus.
+pub fn show(ui: &mut egui::Ui, texture_id: egui::TextureId) -> ViewportLayout {
+    let size = ui.available_size().max(egui::vec2(1.0, 1.0));
+    let image = egui::Image::new(egui::load::SizedTexture::new(texture_id, size))
+        .sense(egui::Sense::click_and_drag());
+    let response = ui.add(image);
+    if response.clicked() || response.drag_started() {
+        response.request_focus();
+    }
+    let pixels_per_point = ui.ctx().pixels_per_point();
+    let rect = response.rect;
+    ViewportLayout {
+        size: [
+            ((rect.width() * pixels_per_point).round() as u32).max(1),
+            ((rect.height() * pixels_per_point).round() as u32).max(1),
+        ],
+        origin: Vec2::new(rect.min.x, rect.min.y) * pixels_per_point,
+        rect,
+        hovered: response.hovered(),
+        focused: response.has_focus(),
+    }
+}
+
+fn create_view(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("scene viewport target"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
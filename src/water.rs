@@ -1,13 +1,148 @@
 use glam::{Mat4, Vec3};
+use std::cell::{Cell, RefCell};
 use std::num::NonZeroU64;
+use std::ops::Range;
 use wgpu::util::DeviceExt;
 
-use crate::terrain::WORLD_RADIUS;
+use crate::depth::{self, DepthMode};
+#[cfg(feature = "hot-reload")]
+use crate::hot_reload;
+use crate::mesh::{self, DrawStats, IndexBuffer};
+use crate::terrain::{self, Grid};
+use crate::view::SceneView;
+use crate::world::WorldConfig;
 
-const MAP_WIDTH: f32 = WORLD_RADIUS * std::f32::consts::TAU;
-const MAP_HEIGHT: f32 = WORLD_RADIUS * std::f32::consts::PI;
-const FLAT_WATER_OFFSET: f32 = 1.2;
-const GLOBE_WATER_OFFSET: f32 = 0.6;
+const DEFAULT_SPECULAR_STRENGTH: f32 = 0.8;
+const DEFAULT_SHININESS: f32 = 96.0;
+const DEFAULT_RESOLUTION: u32 = 128;
+const DEFAULT_EDGE_SOFTNESS: f32 = 1.5;
+const DEFAULT_COLOR: [f32; 3] = [0.08, 0.32, 0.5];
+const DEFAULT_OPACITY: f32 = 0.55;
+const DEFAULT_WAVE_DIRECTION: [f32; 2] = [1.0, 0.3];
+const DEFAULT_WAVELENGTH: f32 = 8.0;
+const DEFAULT_WAVE_AMPLITUDE: f32 = 0.12;
+const DEFAULT_CHOPPINESS: f32 = 0.5;
+/// Matches the classic `glPolygonOffset(1, 1)` for coplanar layers.
+const DEFAULT_DEPTH_BIAS: i32 = 1;
+const DEFAULT_DEPTH_BIAS_SLOPE: f32 = 1.0;
+/// Sum of `WAVE_AMPLITUDES` in water.wgsl: the furthest the waves move the
+/// surface, in units of `wave_amplitude`.
+const WAVE_AMPLITUDE_SUM: f32 = 1.8;
+/// Sum of `WAVE_AMPLITUDES / WAVE_LENGTHS` in water.wgsl; times
+/// `TAU * wave_amplitude / wavelength` it bounds the surface slope.
+const WAVE_SLOPE_SUM: f32 = 2.54;
+pub const MIN_RESOLUTION: u32 = 32;
+pub const MAX_RESOLUTION: u32 = 512;
+/// Classes in the water mask; see `classify_water`.
+const DRY: u8 = 0;
+const OCEAN: u8 = 1;
+const LAKE: u8 = 2;
+
+/// Which standing water the layer draws, going by the water mask.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WaterBodies {
+    /// One sea surface over the whole globe, showing wherever the terrain
+    /// dips below it; the mask is not read.
+    All,
+    /// Only the largest connected body of water.
+    Oceans,
+    /// Only the smaller bodies below the water level, cut off from the ocean.
+    Lakes,
+}
+
+impl WaterBodies {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub const ALL: [WaterBodies; 3] = [WaterBodies::All, WaterBodies::Oceans, WaterBodies::Lakes];
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn label(self) -> &'static str {
+        match self {
+            WaterBodies::All => "Everywhere",
+            WaterBodies::Oceans => "Oceans",
+            WaterBodies::Lakes => "Lakes",
+        }
+    }
+
+    /// Mask class the shader keeps, or 0 to skip the mask.
+    fn class(self) -> u8 {
+        match self {
+            WaterBodies::All => DRY,
+            WaterBodies::Oceans => OCEAN,
+            WaterBodies::Lakes => LAKE,
+        }
+    }
+}
+
+/// Appearance and quality settings for the water layer.
+///
+/// Frame cost: the sphere has `resolution * (resolution + 1)` vertices and
+/// about `2 * resolution^2` triangles, so 128 draws ~33k triangles, 256 ~131k
+/// and 512 ~523k. Shorelines shimmer less once the water matches the terrain
+/// grid (256). Edge softening costs four heightfield loads per water fragment
+/// regardless of its value; 0 disables the fade but not the loads. Keeping
+/// only oceans or only lakes adds four mask loads.
+#[derive(Copy, Clone, Debug)]
+pub struct WaterSettings {
+    /// Draw the water layer at all; off gives a dry planet.
+    pub enabled: bool,
+    /// Deep-water tint; the shoreline fade thins it towards the coast.
+    pub color: [f32; 3],
+    pub opacity: f32,
+    pub specular_strength: f32,
+    pub shininess: f32,
+    /// Latitude rings in the water sphere; changing it rebuilds the mesh.
+    pub resolution: u32,
+    /// Width of the shoreline fade in screen-space derivatives of water depth.
+    pub edge_softness: f32,
+    /// Animate the surface with a small sum of Gerstner waves.
+    pub waves: bool,
+    /// Travel direction on the flat map (x east, y north). On the globe the
+    /// same direction is projected into each point's tangent plane.
+    pub wave_direction: [f32; 2],
+    /// Length of the longest wave in world units; the others are shorter.
+    pub wavelength: f32,
+    pub wave_amplitude: f32,
+    /// Horizontal pull of the vertices towards the crests, from 0 (plain sine
+    /// waves) to 1 (sharpest crests that stay free of loops).
+    pub choppiness: f32,
+    /// Let terrain randomize move the water level as well, within
+    /// `WorldConfig::water_level_jitter`; off keeps the current level.
+    pub jitter_level: bool,
+    /// Constant depth offset pushing the water behind terrain at the same
+    /// depth, so coastlines don't z-fight. In depth-buffer precision steps,
+    /// which are tiny for the float buffer; `depth_bias_slope` does most of
+    /// the work. Changing either rebuilds the pipelines.
+    pub depth_bias: i32,
+    /// Depth offset per unit of the water triangle's depth slope, which
+    /// covers grazing views where the coast is thinnest on screen.
+    pub depth_bias_slope: f32,
+    /// Draw everywhere below the level, or only oceans or only lakes; the
+    /// mask is rebuilt with each new heightfield.
+    pub bodies: WaterBodies,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: DEFAULT_COLOR,
+            opacity: DEFAULT_OPACITY,
+            specular_strength: DEFAULT_SPECULAR_STRENGTH,
+            shininess: DEFAULT_SHININESS,
+            resolution: DEFAULT_RESOLUTION,
+            edge_softness: DEFAULT_EDGE_SOFTNESS,
+            waves: false,
+            wave_direction: DEFAULT_WAVE_DIRECTION,
+            wavelength: DEFAULT_WAVELENGTH,
+            wave_amplitude: DEFAULT_WAVE_AMPLITUDE,
+            choppiness: DEFAULT_CHOPPINESS,
+            jitter_level: false,
+            depth_bias: DEFAULT_DEPTH_BIAS,
+            depth_bias_slope: DEFAULT_DEPTH_BIAS_SLOPE,
+            bodies: WaterBodies::All,
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -21,68 +156,141 @@ struct Vertex {
 struct Globals {
     view_proj: [[f32; 4]; 4],
     morph: [f32; 4],
+    camera_pos: [f32; 4],
+    sun_dir: [f32; 4],
+    depth: [f32; 4],
+    color: [f32; 4],
+    specular: [f32; 4],
+    shore: [f32; 4],
+    waves: [f32; 4],
+    wave_shape: [f32; 4],
 }
 
 pub struct Water {
-    pipeline: wgpu::RenderPipeline,
+    pipelines: [wgpu::RenderPipeline; 2],
+    /// Kept with the layout and format so the pipelines can be rebuilt when
+    /// the depth bias changes, from the current shader.
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
     vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+    index_buffer: IndexBuffer,
     uniform: wgpu::Buffer,
+    /// Contents of the last `uniform` write, so an unchanged view is not
+    /// uploaded again.
+    uploaded: Cell<Option<Globals>>,
+    /// `uniform` writes so far, shown beside the draw stats.
+    uniform_uploads: Cell<u32>,
+    /// Index ranges left after horizon culling the last view; `None` draws
+    /// everything.
+    visible: RefCell<Option<Vec<Range<u32>>>>,
+    stats: Cell<DrawStats>,
     bind_group: wgpu::BindGroup,
-    index_count: u32,
+    settings: WaterSettings,
+    level: f32,
+    /// World of the terrain globe this layer covers.
+    world: WorldConfig,
+    heightfield: wgpu::Texture,
+    /// Per-vertex `DRY`, `OCEAN` or `LAKE`, built from the heightfield.
+    mask: wgpu::Texture,
 }
 
 impl Water {
-    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, height: f32) -> Self {
-        let (vertices, indices) = generate_sphere(WORLD_RADIUS + height - GLOBE_WATER_OFFSET, height);
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("water vertices"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("water indices"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        height: f32,
+        world: &WorldConfig,
+    ) -> Self {
+        let settings = WaterSettings::default();
+        let (vertex_buffer, index_buffer) = create_mesh(device, world, height, settings.resolution);
 
         let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("water globals"),
             contents: bytemuck::bytes_of(&Globals {
                 view_proj: Mat4::IDENTITY.to_cols_array_2d(),
                 morph: [0.0; 4],
+                camera_pos: [0.0; 4],
+                sun_dir: [0.0, 1.0, 0.0, 0.0],
+                depth: [0.0; 4],
+                color: [0.0; 4],
+                specular: [0.0; 4],
+                shore: [0.0; 4],
+                waves: [0.0; 4],
+                wave_shape: [0.0; 4],
             }),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        // Terrain heights, sampled by the fragment shader to find the shoreline.
+        let heightfield = create_grid_texture(
+            device,
+            "water terrain heightfield",
+            world.grid,
+            wgpu::TextureFormat::R32Float,
+        );
+        let heightfield_view = heightfield.create_view(&wgpu::TextureViewDescriptor::default());
+        let mask = create_grid_texture(
+            device,
+            "water mask",
+            world.grid,
+            wgpu::TextureFormat::R8Uint,
+        );
+        let mask_view = mask.create_view(&wgpu::TextureViewDescriptor::default());
+
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("water bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(std::mem::size_of::<Globals>() as u64),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(std::mem::size_of::<Globals>() as u64),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("water bind group"),
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform.as_entire_binding(),
-            }],
-        });
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("water shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/water.wgsl").into()),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&heightfield_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&mask_view),
+                },
+            ],
         });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -91,11 +299,370 @@ impl Water {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let shader = create_shader(device, include_str!("../shaders/water.wgsl"));
+        let pipelines = create_pipelines(device, &pipeline_layout, format, &shader, &settings);
+
+        Self {
+            pipelines,
+            shader,
+            pipeline_layout,
+            format,
+            vertex_buffer,
+            index_buffer,
+            uniform,
+            uploaded: Cell::new(None),
+            uniform_uploads: Cell::new(0),
+            visible: RefCell::new(None),
+            stats: Cell::new(DrawStats::default()),
+            bind_group,
+            settings,
+            level: height,
+            world: *world,
+            heightfield,
+            mask,
+        }
+    }
+
+    /// Rebuilds the pipelines from new WGSL, keeping the current ones if it
+    /// fails to compile or validate.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_shader(&mut self, device: &wgpu::Device, source: &str) -> Result<(), String> {
+        (self.shader, self.pipelines) = hot_reload::validated(device, || {
+            let shader = create_shader(device, source);
+            let pipelines = create_pipelines(
+                device,
+                &self.pipeline_layout,
+                self.format,
+                &shader,
+                &self.settings,
+            );
+            (shader, pipelines)
+        })?;
+        Ok(())
+    }
+
+    pub fn update_view(&self, queue: &wgpu::Queue, view: &SceneView) {
+        self.cull(view);
+        let map = terrain::map_size(self.world.radius);
+        let globals = Globals {
+            view_proj: view.view_proj.to_cols_array_2d(),
+            morph: [view.morph.clamp(0.0, 1.0), view.rotation, map.x, map.y],
+            camera_pos: view.eye.extend(1.0).into(),
+            sun_dir: view.sun_dir.extend(0.0).into(),
+            depth: view.depth_mode.uniform(view.far),
+            color: [
+                self.settings.color[0],
+                self.settings.color[1],
+                self.settings.color[2],
+                self.settings.opacity,
+            ],
+            specular: [
+                self.settings.specular_strength,
+                self.settings.shininess,
+                0.0,
+                0.0,
+            ],
+            shore: [
+                self.world.radius,
+                self.settings.edge_softness,
+                self.settings.bodies.class() as f32,
+                0.0,
+            ],
+            waves: if self.settings.waves {
+                [
+                    1.0,
+                    view.time,
+                    self.settings.wave_amplitude,
+                    self.settings.wavelength,
+                ]
+            } else {
+                // Zero time too, so a still surface never needs re-uploading.
+                [0.0; 4]
+            },
+            wave_shape: [
+                self.settings.wave_direction[0],
+                self.settings.wave_direction[1],
+                self.settings.choppiness,
+                // Widest vertex spacing (along the equator), below which the
+                // shader drops waves from the geometry and keeps them in the
+                // normals only.
+                map.x / self.settings.resolution as f32,
+            ],
+        };
+        let unchanged = self
+            .uploaded
+            .get()
+            .is_some_and(|last| bytemuck::bytes_of(&last) == bytemuck::bytes_of(&globals));
+        if !unchanged {
+            queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&globals));
+            self.uploaded.set(Some(globals));
+            self.uniform_uploads.set(self.uniform_uploads.get() + 1);
+        }
+    }
+
+    /// Keeps the quads that may face `view.eye` when horizon culling is on.
+    /// Past the sphere's horizon the surface faces away and is culled on
+    /// the GPU anyway; waves widen that by their height and slope.
+    fn cull(&self, view: &SceneView) {
+        let (lift, tilt) = if self.settings.waves {
+            let amplitude = self.settings.wave_amplitude;
+            let slope = std::f32::consts::TAU * amplitude / self.settings.wavelength.max(1e-3);
+            let tilt = (slope * WAVE_SLOPE_SUM).atan();
+            (amplitude * WAVE_AMPLITUDE_SUM, tilt)
+        } else {
+            (0.0, 0.0)
+        };
+        let inner = self.world.radius + self.level - self.world.globe_water_offset - lift;
+        let distance = view.eye.length();
+        let outer = inner + 2.0 * lift;
+        let cull = view.horizon_cull && view.morph <= 0.0 && inner > 0.0 && distance > outer;
+        let visible = if cull {
+            let reach = (inner / distance).acos() + tilt;
+            let resolution = self.settings.resolution;
+            mesh::horizon_ranges(resolution - 1, resolution, view.eye, reach)
+        } else {
+            None
+        };
+        let count = self.index_buffer.count();
+        self.stats.set(DrawStats::new(visible.as_deref(), count));
+        *self.visible.borrow_mut() = visible;
+    }
+
+    /// What the last `draw` submitted, after any horizon culling.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn draw_stats(&self) -> DrawStats {
+        self.stats.get()
+    }
+
+    /// View uniform writes so far; `update_view` skips an unchanged one.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn uniform_uploads(&self) -> u32 {
+        self.uniform_uploads.get()
+    }
+
+    pub fn settings(&self) -> WaterSettings {
+        self.settings
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn set_settings(&mut self, device: &wgpu::Device, settings: WaterSettings) {
+        let resolution = settings.resolution.clamp(MIN_RESOLUTION, MAX_RESOLUTION);
+        if resolution != self.settings.resolution {
+            (self.vertex_buffer, self.index_buffer) =
+                create_mesh(device, &self.world, self.level, resolution);
+            // The old ranges index the old mesh; redone on the next view.
+            *self.visible.get_mut() = None;
+        }
+        let rebias = settings.depth_bias != self.settings.depth_bias
+            || settings.depth_bias_slope != self.settings.depth_bias_slope;
+        self.settings = WaterSettings {
+            resolution,
+            ..settings
+        };
+        if rebias {
+            self.pipelines = create_pipelines(
+                device,
+                &self.pipeline_layout,
+                self.format,
+                &self.shader,
+                &self.settings,
+            );
+        }
+    }
+
+    /// Moves the surface to `level`, rebuilding the mesh if it changed.
+    /// Appearance settings are kept.
+    pub fn set_level(&mut self, device: &wgpu::Device, level: f32) {
+        if level == self.level {
+            return;
+        }
+        (self.vertex_buffer, self.index_buffer) =
+            create_mesh(device, &self.world, level, self.settings.resolution);
+        *self.visible.get_mut() = None;
+        self.level = level;
+    }
+
+    /// Uploads the terrain heightfield (row-major, `Grid::lon` per row) used
+    /// for shoreline softening, along with the water mask it gives at the
+    /// current level. Call it after `set_level` when both change.
+    pub fn set_heightfield(&self, queue: &wgpu::Queue, heights: &[f32]) {
+        let grid = self.world.grid;
+        let bytes = bytemuck::cast_slice(heights);
+        write_grid_texture(queue, &self.heightfield, grid, bytes);
+        let mask = classify_water(heights, grid, self.level);
+        write_grid_texture(queue, &self.mask, grid, &mask);
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, depth_mode: DepthMode) {
+        pass.set_pipeline(&self.pipelines[depth_mode.is_reversed() as usize]);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        self.index_buffer
+            .draw_ranges(pass, self.visible.borrow().as_deref());
+    }
+}
+
+/// A texture with one texel per terrain grid vertex, for the fragment shader
+/// to load from.
+fn create_grid_texture(
+    device: &wgpu::Device,
+    label: &str,
+    grid: Grid,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: grid.lon,
+            height: grid.lat,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+/// Uploads row-major `bytes` covering the whole of a `create_grid_texture`.
+fn write_grid_texture(queue: &wgpu::Queue, texture: &wgpu::Texture, grid: Grid, bytes: &[u8]) {
+    let extent = wgpu::Extent3d {
+        width: grid.lon,
+        height: grid.lat,
+        depth_or_array_layers: 1,
+    };
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytes,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes.len() as u32 / grid.lat),
+            rows_per_image: Some(grid.lat),
+        },
+        extent,
+    );
+}
+
+/// Sorts every grid vertex into `DRY`, `OCEAN` or `LAKE`. Vertices below
+/// `level` join into bodies of water through their row and column
+/// neighbours, wrapping across the seam meridian, whose column each row
+/// repeats. The largest body is the ocean and any others are lakes. The
+/// map's edges are not treated as shore: on the globe they are the seam and
+/// the poles, which water simply crosses.
+fn classify_water(heights: &[f32], grid: Grid, level: f32) -> Vec<u8> {
+    let mut mask: Vec<u8> = heights
+        .iter()
+        .map(|&height| if height < level { LAKE } else { DRY })
+        .collect();
+    let (last_x, last_z) = (grid.lon - 1, grid.lat - 1);
+    // Which body each wet vertex belongs to, and how many vertices each has.
+    let mut body = vec![usize::MAX; mask.len()];
+    let mut sizes = Vec::new();
+    for start in 0..mask.len() {
+        if mask[start] == DRY || body[start] != usize::MAX {
+            continue;
+        }
+        let index = sizes.len();
+        let mut size = 0;
+        body[start] = index;
+        let mut pending = vec![start];
+        while let Some(idx) = pending.pop() {
+            size += 1;
+            let (x, z) = (idx as u32 % grid.lon, idx as u32 / grid.lon);
+            let west = if x == 0 { last_x } else { x - 1 };
+            let east = if x == last_x { 0 } else { x + 1 };
+            let north = (z > 0).then(|| (x, z - 1));
+            let south = (z < last_z).then(|| (x, z + 1));
+            let neighbours = [Some((west, z)), Some((east, z)), north, south];
+            for (x, z) in neighbours.into_iter().flatten() {
+                let next = grid.index(x, z);
+                if mask[next] != DRY && body[next] == usize::MAX {
+                    body[next] = index;
+                    pending.push(next);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    let ocean = (0..sizes.len()).max_by_key(|&index| sizes[index]);
+    for (class, body) in mask.iter_mut().zip(body) {
+        if Some(body) == ocean {
+            *class = OCEAN;
+        }
+    }
+    mask
+}
+
+fn create_mesh(
+    device: &wgpu::Device,
+    world: &WorldConfig,
+    height: f32,
+    resolution: u32,
+) -> (wgpu::Buffer, IndexBuffer) {
+    let radius = world.radius + height - world.globe_water_offset;
+    let flat_height = height - world.flat_water_offset;
+    let (vertices, indices) = generate_sphere(radius, flat_height, resolution);
+    debug_assert_winding(&vertices, &indices, world.radius);
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("water vertices"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let index_buffer = IndexBuffer::new(device, "water indices", &indices, vertices.len());
+
+    (vertex_buffer, index_buffer)
+}
+
+/// Checks the sphere faces outward on the globe and upward on the flat map,
+/// so an inverted winding fails in debug builds rather than culling the sea.
+fn debug_assert_winding(vertices: &[Vertex], indices: &[u32], radius: f32) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let globe: Vec<Vec3> = vertices.iter().map(|v| Vec3::from(v.pos)).collect();
+    mesh::debug_assert_winding("water globe", &globe, indices, |centroid| centroid);
+    let map = terrain::map_size(radius);
+    let flat: Vec<Vec3> = vertices
+        .iter()
+        .map(|v| {
+            let [u, v, height] = v.flat_pos;
+            Vec3::new((u - 0.5) * map.x, height, (0.5 - v) * map.y)
+        })
+        .collect();
+    mesh::debug_assert_winding("water map", &flat, indices, |_| Vec3::Y);
+}
+
+fn create_shader(device: &wgpu::Device, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("water shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+/// Builds the water pipelines for both depth compare directions, biased away
+/// from the camera by `settings`.
+fn create_pipelines(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    shader: &wgpu::ShaderModule,
+    settings: &WaterSettings,
+) -> [wgpu::RenderPipeline; 2] {
+    depth::COMPARES.map(|compare| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("water pipeline"),
-            layout: Some(&pipeline_layout),
+            layout: Some(pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 buffers: &[wgpu::VertexBufferLayout {
                     array_stride: std::mem::size_of::<Vertex>() as u64,
@@ -104,7 +671,7 @@ impl Water {
                 }],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format,
@@ -118,81 +685,45 @@ impl Water {
                 ..Default::default()
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
+                format: depth::FORMAT,
                 depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: compare,
                 stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
+                bias: depth::bias_away(compare, settings.depth_bias, settings.depth_bias_slope),
             }),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
-        });
-
-        Self {
-            pipeline,
-            vertex_buffer,
-            index_buffer,
-            uniform,
-            bind_group,
-            index_count: indices.len() as u32,
-        }
-    }
-
-    pub fn update_view(
-        &self,
-        queue: &wgpu::Queue,
-        view_proj: Mat4,
-        morph: f32,
-        rotation: f32,
-    ) {
-        let globals = Globals {
-            view_proj: view_proj.to_cols_array_2d(),
-            morph: [
-                morph.clamp(0.0, 1.0),
-                rotation,
-                MAP_WIDTH,
-                MAP_HEIGHT,
-            ],
-        };
-        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&globals));
-    }
-
-    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
-        pass.set_pipeline(&self.pipeline);
-        pass.set_bind_group(0, &self.bind_group, &[]);
-        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        pass.draw_indexed(0..self.index_count, 0, 0..1);
-    }
+        })
+    })
 }
 
-const WATER_RES: u32 = 128;
-const WATER_LON: u32 = WATER_RES + 1;
-
-fn generate_sphere(radius: f32, height: f32) -> (Vec<Vertex>, Vec<u32>) {
-    let mut vertices = Vec::with_capacity((WATER_RES * WATER_LON) as usize);
-    for z in 0..WATER_RES {
-        let v = z as f32 / (WATER_RES - 1) as f32;
+/// A `resolution`-row sphere of `radius` whose flat-map vertices sit at
+/// `flat_height`.
+fn generate_sphere(radius: f32, flat_height: f32, resolution: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let lon_points = resolution + 1;
+    let mut vertices = Vec::with_capacity((resolution * lon_points) as usize);
+    for z in 0..resolution {
+        let v = z as f32 / (resolution - 1) as f32;
         let lat = v * std::f32::consts::PI;
         let sin_lat = lat.sin();
         let cos_lat = lat.cos();
-        for x in 0..WATER_LON {
-            let u = x as f32 / (WATER_LON - 1) as f32;
+        for x in 0..lon_points {
+            let u = x as f32 / (lon_points - 1) as f32;
             let lon = u * std::f32::consts::TAU;
             let dir = Vec3::new(lon.cos() * sin_lat, cos_lat, lon.sin() * sin_lat);
             vertices.push(Vertex {
                 pos: (dir * radius).into(),
-                flat_pos: [u, v, height - FLAT_WATER_OFFSET],
+                flat_pos: [u, v, flat_height],
             });
         }
     }
 
-    let mut indices = Vec::with_capacity(((WATER_RES - 1) * (WATER_LON - 1) * 6) as usize);
-    for z in 0..WATER_RES - 1 {
-        for x in 0..WATER_LON - 1 {
-            let i0 = z * WATER_LON + x;
+    let mut indices = Vec::with_capacity(((resolution - 1) * (lon_points - 1) * 6) as usize);
+    for z in 0..resolution - 1 {
+        for x in 0..lon_points - 1 {
+            let i0 = z * lon_points + x;
             let i1 = i0 + 1;
-            let i2 = i0 + WATER_LON;
+            let i2 = i0 + lon_points;
             let i3 = i2 + 1;
             indices.extend_from_slice(&[i0, i1, i2, i1, i3, i2]);
         }
@@ -200,3 +731,64 @@ fn generate_sphere(radius: f32, height: f32) -> (Vec<Vertex>, Vec<u32>) {
 
     (vertices, indices)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::DEFAULT_RADIUS;
+
+    #[test]
+    fn sphere_sits_at_the_requested_radius() {
+        for radius in [DEFAULT_RADIUS, 2.0 * DEFAULT_RADIUS] {
+            let (vertices, _) = generate_sphere(radius, 0.0, 16);
+            for vertex in &vertices {
+                let error = (Vec3::from(vertex.pos).length() - radius).abs();
+                assert!(error < radius * 1e-5, "{error} off radius {radius}");
+            }
+            // The flat copy spans the whole map whatever the radius.
+            let corners = [vertices[0].flat_pos, vertices[vertices.len() - 1].flat_pos];
+            assert_eq!(corners, [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+        }
+    }
+
+    #[test]
+    fn sphere_winds_outward() {
+        let world = WorldConfig::default();
+        let (vertices, indices) = generate_sphere(world.radius, 0.0, 32);
+        debug_assert_winding(&vertices, &indices, world.radius);
+    }
+
+    #[test]
+    fn largest_body_is_ocean_across_the_seam_and_poles() {
+        let grid = Grid::new(16);
+        let last_x = grid.lon - 1;
+        let ocean = |_: u32, z: u32| (3..=6).contains(&z);
+        let inland = |x: u32, z: u32| (8..=9).contains(&x) && z == 10;
+        // Either side of the seam meridian, which columns 0 and `last_x` share.
+        let seam = |x: u32, z: u32| (x <= 1 || x >= last_x - 1) && (11..=12).contains(&z);
+        // The whole north pole row, plus one vertex below it.
+        let polar = |x: u32, z: u32| z == 0 || (x, z) == (4, 1);
+        let mut heights = vec![1.0; grid.points()];
+        for z in 0..grid.lat {
+            for x in 0..grid.lon {
+                if ocean(x, z) || inland(x, z) || seam(x, z) || polar(x, z) {
+                    heights[grid.index(x, z)] = -1.0;
+                }
+            }
+        }
+
+        let mask = classify_water(&heights, grid, 0.0);
+        for z in 0..grid.lat {
+            for x in 0..grid.lon {
+                let expected = if ocean(x, z) {
+                    OCEAN
+                } else if inland(x, z) || seam(x, z) || polar(x, z) {
+                    LAKE
+                } else {
+                    DRY
+                };
+                assert_eq!(mask[grid.index(x, z)], expected, "vertex ({x}, {z})");
+            }
+        }
+    }
+}
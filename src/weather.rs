@@ -0,0 +1,435 @@
+use glam::{Mat4, Vec3};
+use std::cell::Cell;
+use std::num::NonZeroU64;
+use wgpu::util::DeviceExt;
+
+use crate::depth::{self, DepthMode};
+#[cfg(feature = "hot-reload")]
+use crate::hot_reload;
+use crate::view::SceneView;
+
+/// Particles at full density; the storage buffer is sized for this many.
+pub const MAX_PARTICLES: u32 = 65_536;
+const DEFAULT_DENSITY: f32 = 0.25;
+const DEFAULT_EXTENT: f32 = 24.0;
+pub const MIN_EXTENT: f32 = 4.0;
+pub const MAX_EXTENT: f32 = 80.0;
+/// Longest step the simulation takes, so a stalled frame doesn't fling every
+/// particle through the box at once.
+const MAX_STEP: f32 = 0.1;
+const WORKGROUP_SIZE: u32 = 64;
+const RAIN_COLOR: [f32; 4] = [0.7, 0.76, 0.86, 0.45];
+const SNOW_COLOR: [f32; 4] = [0.95, 0.97, 1.0, 0.9];
+
+/// Rain and snow over the part of the terrain the camera looks at.
+///
+/// Frame cost: one compute invocation and one six-vertex quad per particle,
+/// so full density adds ~131k triangles of alpha-blended overdraw.
+#[derive(Copy, Clone, Debug)]
+pub struct WeatherSettings {
+    pub enabled: bool,
+    /// Share of `MAX_PARTICLES` simulated and drawn, in [0, 1].
+    pub density: f32,
+    /// Half the side of the box the particles fill, in world units.
+    pub extent: f32,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            density: DEFAULT_DENSITY,
+            extent: DEFAULT_EXTENT,
+        }
+    }
+}
+
+impl WeatherSettings {
+    fn particle_count(&self) -> u32 {
+        (self.density.clamp(0.0, 1.0) * MAX_PARTICLES as f32) as u32
+    }
+}
+
+/// Where the particles fall this frame.
+#[derive(Copy, Clone, Debug)]
+pub struct Region {
+    /// Terrain point at the middle of the view; the box sits just above it.
+    pub surface: Vec3,
+    /// Local up at `surface`; particles fall against it.
+    pub up: Vec3,
+    /// Snow rather than rain, from the temperature at `surface`.
+    pub snow: bool,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Globals {
+    view_proj: [[f32; 4]; 4],
+    camera_pos: [f32; 4],
+    region: [f32; 4],
+    fall: [f32; 4],
+    depth: [f32; 4],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Step {
+    region: [f32; 4],
+    fall: [f32; 4],
+    motion: [f32; 4],
+}
+
+pub struct Weather {
+    compute_pipeline: wgpu::ComputePipeline,
+    pipelines: [wgpu::RenderPipeline; 2],
+    #[cfg(feature = "hot-reload")]
+    compute_layout: wgpu::PipelineLayout,
+    #[cfg(feature = "hot-reload")]
+    render_layout: wgpu::PipelineLayout,
+    #[cfg(feature = "hot-reload")]
+    format: wgpu::TextureFormat,
+    uniform: wgpu::Buffer,
+    /// Contents of the last `uniform` write, so an unchanged view is not
+    /// uploaded again.
+    uploaded: Cell<Option<Globals>>,
+    step: wgpu::Buffer,
+    render_bind_group: wgpu::BindGroup,
+    compute_bind_group: wgpu::BindGroup,
+    settings: WeatherSettings,
+    /// Box center, half extent and fall direction of the last `update`;
+    /// `None` while there is nothing to draw.
+    region: Option<([f32; 4], [f32; 4])>,
+    count: u32,
+    /// Animation time of the last `update`, to step the particles by.
+    time: Option<f32>,
+    /// Set by `update` until `simulate` records the step, so a frame drawn
+    /// more than once (stereo eyes, screenshots) only moves particles once.
+    pending: Cell<bool>,
+}
+
+impl Weather {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("weather globals"),
+            contents: bytemuck::bytes_of(&Globals {
+                view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+                camera_pos: [0.0; 4],
+                region: [0.0, 0.0, 0.0, 1.0],
+                fall: [0.0, -1.0, 0.0, 0.0],
+                depth: [0.0; 4],
+                color: [0.0; 4],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let step = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("weather step"),
+            contents: bytemuck::bytes_of(&Step {
+                region: [0.0, 0.0, 0.0, 1.0],
+                fall: [0.0, -1.0, 0.0, 0.0],
+                motion: [0.0; 4],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Zeroed, which the compute shader reads as "not spawned yet".
+        let particles = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("weather particles"),
+            size: MAX_PARTICLES as u64 * std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let render_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("weather render bind group layout"),
+            entries: &[
+                uniform_entry(
+                    0,
+                    wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    std::mem::size_of::<Globals>(),
+                ),
+                storage_entry(1, wgpu::ShaderStages::VERTEX, true),
+            ],
+        });
+        let compute_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("weather compute bind group layout"),
+            entries: &[
+                uniform_entry(2, wgpu::ShaderStages::COMPUTE, std::mem::size_of::<Step>()),
+                storage_entry(3, wgpu::ShaderStages::COMPUTE, false),
+            ],
+        });
+
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("weather render bind group"),
+            layout: &render_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.as_entire_binding(),
+                },
+            ],
+        });
+        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("weather compute bind group"),
+            layout: &compute_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: step.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: particles.as_entire_binding(),
+                },
+            ],
+        });
+
+        let render_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("weather render pipeline layout"),
+            bind_group_layouts: &[&render_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("weather compute pipeline layout"),
+            bind_group_layouts: &[&compute_layout],
+            push_constant_ranges: &[],
+        });
+
+        let (compute_pipeline, pipelines) = create_pipelines(
+            device,
+            &compute_layout,
+            &render_layout,
+            format,
+            include_str!("../shaders/weather.wgsl"),
+        );
+
+        Self {
+            compute_pipeline,
+            pipelines,
+            #[cfg(feature = "hot-reload")]
+            compute_layout,
+            #[cfg(feature = "hot-reload")]
+            render_layout,
+            #[cfg(feature = "hot-reload")]
+            format,
+            uniform,
+            uploaded: Cell::new(None),
+            step,
+            render_bind_group,
+            compute_bind_group,
+            settings: WeatherSettings::default(),
+            region: None,
+            count: 0,
+            time: None,
+            pending: Cell::new(false),
+        }
+    }
+
+    /// Rebuilds the simulation and render pipelines from new WGSL, keeping
+    /// the current ones if it fails to compile or validate.
+    #[cfg(feature = "hot-reload")]
+    pub fn reload_shader(&mut self, device: &wgpu::Device, source: &str) -> Result<(), String> {
+        (self.compute_pipeline, self.pipelines) = hot_reload::validated(device, || {
+            create_pipelines(
+                device,
+                &self.compute_layout,
+                &self.render_layout,
+                self.format,
+                source,
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn settings(&self) -> WeatherSettings {
+        self.settings
+    }
+
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn set_settings(&mut self, settings: WeatherSettings) {
+        self.settings = WeatherSettings {
+            density: settings.density.clamp(0.0, 1.0),
+            extent: settings.extent.clamp(MIN_EXTENT, MAX_EXTENT),
+            ..settings
+        };
+    }
+
+    /// Moves the particle box to `region` and queues one simulation step
+    /// covering the animation time since the last update. `None`, or the
+    /// layer being disabled, hides the particles and holds them still.
+    pub fn update(&mut self, queue: &wgpu::Queue, region: Option<Region>, time: f32) {
+        let previous = self.time.replace(time);
+        let count = self.settings.particle_count();
+        let region = match region {
+            Some(region) if self.settings.enabled && count > 0 => region,
+            _ => {
+                self.region = None;
+                return;
+            }
+        };
+        let half = self.settings.extent;
+        let up = region.up.normalize_or(Vec3::Y);
+        let center = region.surface + up * (half * 0.5);
+        let bounds = center.extend(half).into();
+        let fall = (-up).extend(region.snow as u32 as f32).into();
+        // Paused time gives a zero step: the particles hang where they are.
+        let dt = previous.map_or(0.0, |last| (time - last).clamp(0.0, MAX_STEP));
+        let step = Step {
+            region: bounds,
+            fall,
+            motion: [dt, time, count as f32, 0.0],
+        };
+        queue.write_buffer(&self.step, 0, bytemuck::bytes_of(&step));
+        self.region = Some((bounds, fall));
+        self.count = count;
+        self.pending.set(true);
+    }
+
+    pub fn update_view(&self, queue: &wgpu::Queue, view: &SceneView) {
+        let Some((region, fall)) = self.region else {
+            return;
+        };
+        let globals = Globals {
+            view_proj: view.view_proj.to_cols_array_2d(),
+            camera_pos: view.eye.extend(1.0).into(),
+            region,
+            fall,
+            depth: view.depth_mode.uniform(view.far),
+            color: if fall[3] > 0.5 {
+                SNOW_COLOR
+            } else {
+                RAIN_COLOR
+            },
+        };
+        let unchanged = self
+            .uploaded
+            .get()
+            .is_some_and(|last| bytemuck::bytes_of(&last) == bytemuck::bytes_of(&globals));
+        if !unchanged {
+            queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&globals));
+            self.uploaded.set(Some(globals));
+        }
+    }
+
+    /// Whether the last `update` left anything to draw.
+    pub fn is_visible(&self) -> bool {
+        self.region.is_some()
+    }
+
+    /// Records the queued simulation step, if any, as a compute pass. Must
+    /// come before the render pass that draws the particles.
+    pub fn simulate(&self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.pending.replace(false) || !self.is_visible() {
+            return;
+        }
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("weather simulation pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.compute_pipeline);
+        pass.set_bind_group(0, &self.compute_bind_group, &[]);
+        pass.dispatch_workgroups(self.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, depth_mode: DepthMode) {
+        if !self.is_visible() {
+            return;
+        }
+        pass.set_pipeline(&self.pipelines[depth_mode.is_reversed() as usize]);
+        pass.set_bind_group(0, &self.render_bind_group, &[]);
+        pass.draw(0..6, 0..self.count);
+    }
+}
+
+fn uniform_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    size: usize,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size as u64),
+        },
+        count: None,
+    }
+}
+
+fn storage_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(std::mem::size_of::<[f32; 4]>() as u64),
+        },
+        count: None,
+    }
+}
+
+/// Compiles the weather shader and builds its simulation pipeline and its
+/// render pipelines for both depth compare directions.
+fn create_pipelines(
+    device: &wgpu::Device,
+    compute_layout: &wgpu::PipelineLayout,
+    render_layout: &wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    source: &str,
+) -> (wgpu::ComputePipeline, [wgpu::RenderPipeline; 2]) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("weather shader"),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    let compute = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("weather simulation pipeline"),
+        layout: Some(compute_layout),
+        module: &shader,
+        entry_point: "cs_main",
+    });
+
+    let render = depth::COMPARES.map(|compare| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("weather pipeline"),
+            layout: Some(render_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            // Billboards turn to face the camera, so neither side is culled.
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth::FORMAT,
+                depth_write_enabled: false,
+                depth_compare: compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    });
+
+    (compute, render)
+}
@@ -0,0 +1,224 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::terrain::Grid;
+use crate::transition::MapTransition;
+
+const DEFAULT_GRID_ROWS: u32 = 256;
+/// Globe radius used unless `--radius` or a world file asks for another.
+pub const DEFAULT_RADIUS: f32 = 100.0;
+const DEFAULT_HEIGHT_AMPLITUDE: f32 = 10.0;
+const DEFAULT_WATER_LEVEL: f32 = 0.0;
+const DEFAULT_WATER_LEVEL_JITTER: f32 = 0.8;
+const DEFAULT_FLAT_WATER_OFFSET: f32 = 1.2;
+const DEFAULT_GLOBE_WATER_OFFSET: f32 = 0.6;
+const DEFAULT_FOV_Y_DEGREES: f32 = 50.0;
+const DEFAULT_MAP_FRAMING_PITCH: f32 = -1.25;
+/// Fewer rows leave no interior quads between the poles to sample.
+const MIN_GRID_ROWS: u32 = 2;
+/// Keeps every vertex index within `u32` with room to spare.
+const MAX_GRID_ROWS: u32 = 4096;
+
+/// Tunables fixed for the whole session: the terrain grid, the globe's size
+/// and relief, where the sea sits and how the camera frames it all. Built
+/// once in `State::new`, from the defaults or a world file, and handed to
+/// `Terrain::new` and `Water::new`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WorldConfig {
+    pub grid: Grid,
+    /// Globe radius in world units. Terrain heights and clearances stay in
+    /// absolute units, so a larger globe has proportionally gentler relief.
+    pub radius: f32,
+    /// Height scale of the terrain noise. The biome height bands and the
+    /// temperature lapse rate are measured in multiples of it.
+    pub height_amplitude: f32,
+    /// Sea surface height the terrain starts at.
+    pub water_level: f32,
+    /// Furthest a jittered randomize moves the water level off `water_level`.
+    pub water_level_jitter: f32,
+    /// Depth of the water mesh below the sea surface on the flat map.
+    pub flat_water_offset: f32,
+    /// Depth of the water mesh below the sea surface on the globe.
+    pub globe_water_offset: f32,
+    pub fov_y_degrees: f32,
+    /// Camera pitch used when reframing onto the flat map; close to top-down
+    /// but tilted enough to read the relief.
+    pub map_framing_pitch: f32,
+    /// How the globe-to-map blend moves; the overlay can still change it.
+    pub map_transition: MapTransition,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            grid: Grid::new(DEFAULT_GRID_ROWS),
+            radius: DEFAULT_RADIUS,
+            height_amplitude: DEFAULT_HEIGHT_AMPLITUDE,
+            water_level: DEFAULT_WATER_LEVEL,
+            water_level_jitter: DEFAULT_WATER_LEVEL_JITTER,
+            flat_water_offset: DEFAULT_FLAT_WATER_OFFSET,
+            globe_water_offset: DEFAULT_GLOBE_WATER_OFFSET,
+            fov_y_degrees: DEFAULT_FOV_Y_DEGREES,
+            map_framing_pitch: DEFAULT_MAP_FRAMING_PITCH,
+            map_transition: MapTransition::default(),
+        }
+    }
+}
+
+impl WorldConfig {
+    /// Reads a world file; see `parse`.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("reading {}: {err}", path.display()))?;
+        Self::parse(&text).map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+
+    /// Parses `key = value` lines, one per field, keeping the defaults for
+    /// anything left out. `grid` is the number of latitude rows, and
+    /// `flatten_seconds` or `map_transition_speed` picks a fixed or an
+    /// exponential map transition. Blank lines and `#` comments are skipped.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut config = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+            config
+                .set(key.trim(), value.trim())
+                .map_err(|err| format!("line {}: {err}", number + 1))?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        match key {
+            "grid" => self.grid = Grid::new(value.parse()?),
+            "radius" => self.radius = value.parse()?,
+            "height_amplitude" => self.height_amplitude = value.parse()?,
+            "water_level" => self.water_level = value.parse()?,
+            "water_level_jitter" => self.water_level_jitter = value.parse()?,
+            "flat_water_offset" => self.flat_water_offset = value.parse()?,
+            "globe_water_offset" => self.globe_water_offset = value.parse()?,
+            "fov_y_degrees" => self.fov_y_degrees = value.parse()?,
+            "map_framing_pitch" => self.map_framing_pitch = value.parse()?,
+            "flatten_seconds" => {
+                self.map_transition = MapTransition::Fixed {
+                    seconds: value.parse()?,
+                }
+            }
+            "map_transition_speed" => {
+                self.map_transition = MapTransition::Exponential {
+                    speed: value.parse()?,
+                }
+            }
+            _ => return Err(format!("unknown key `{key}`").into()),
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let rows = self.grid.lat;
+        if !(MIN_GRID_ROWS..=MAX_GRID_ROWS).contains(&rows) {
+            return Err(format!(
+                "grid must be {MIN_GRID_ROWS}-{MAX_GRID_ROWS} rows but got {rows}"
+            )
+            .into());
+        }
+        let positive = [
+            ("radius", self.radius),
+            ("height_amplitude", self.height_amplitude),
+        ];
+        for (name, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{name} must be positive but got {value}").into());
+            }
+        }
+        if !(self.fov_y_degrees > 0.0 && self.fov_y_degrees < 180.0) {
+            let fov = self.fov_y_degrees;
+            return Err(format!("fov_y_degrees must be between 0 and 180 but got {fov}").into());
+        }
+        let transition = match self.map_transition {
+            MapTransition::Exponential { speed } => ("map_transition_speed", speed),
+            MapTransition::Fixed { seconds } => ("flatten_seconds", seconds),
+        };
+        if !(transition.1.is_finite() && transition.1 > 0.0) {
+            let (name, value) = transition;
+            return Err(format!("{name} must be positive but got {value}").into());
+        }
+        if self.water_level_jitter < 0.0 {
+            let jitter = self.water_level_jitter;
+            return Err(format!("water_level_jitter must not be negative but got {jitter}").into());
+        }
+        let finite = [
+            self.water_level,
+            self.water_level_jitter,
+            self.flat_water_offset,
+            self.globe_water_offset,
+            self.map_framing_pitch,
+        ];
+        if !finite.iter().all(|value| value.is_finite()) {
+            return Err("world values must be finite".into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        WorldConfig::parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse_reads_keys_over_the_defaults() {
+        let text = "# a small world\n\ngrid = 64\nradius = 50 # half size\nflatten_seconds = 2\n";
+        let config = WorldConfig::parse(text).unwrap();
+        assert_eq!(config.grid, Grid::new(64));
+        assert_eq!(config.radius, 50.0);
+        assert_eq!(config.map_transition, MapTransition::Fixed { seconds: 2.0 });
+        assert_eq!(config.fov_y_degrees, DEFAULT_FOV_Y_DEGREES);
+        assert_eq!(WorldConfig::parse("").unwrap(), WorldConfig::default());
+    }
+
+    #[test]
+    fn parse_reports_the_line_of_a_malformed_entry() {
+        assert_eq!(error("grid = 64\nsize = 3"), "line 2: unknown key `size`");
+        assert_eq!(error("\n\nradius 50"), "line 3: expected key = value");
+        assert!(error("radius = big").starts_with("line 1: "));
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        // Each message names the key it rejects.
+        let cases = [
+            ("grid = 1", "must be 2-4096 rows but got 1"),
+            ("grid = 4097", "must be 2-4096 rows but got 4097"),
+            ("radius = 0", "must be positive but got 0"),
+            ("radius = -3", "must be positive but got -3"),
+            ("radius = inf", "must be positive but got inf"),
+            ("height_amplitude = 0", "must be positive but got 0"),
+            ("flatten_seconds = 0", "must be positive but got 0"),
+            ("map_transition_speed = -1", "must be positive but got -1"),
+            ("water_level_jitter = -1", "must not be negative but got -1"),
+        ];
+        for (text, expected) in cases {
+            let key = text.split(' ').next().unwrap();
+            assert_eq!(error(text), format!("{key} {expected}"));
+        }
+        for fov in ["0", "180", "NaN"] {
+            let expected = format!("fov_y_degrees must be between 0 and 180 but got {fov}");
+            assert_eq!(error(&format!("fov_y_degrees = {fov}")), expected);
+        }
+        assert_eq!(error("water_level = NaN"), "world values must be finite");
+        // The bounds themselves are allowed.
+        assert!(WorldConfig::parse("grid = 2\nwater_level_jitter = 0").is_ok());
+        assert!(WorldConfig::parse("grid = 4096").is_ok());
+    }
+}